@@ -0,0 +1,246 @@
+use crate::{econ_data::field_to_u128, F, FV};
+
+use ark_ff::ToConstraintField;
+use ark_r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    uint8::UInt8,
+    R1CSVar, ToBytesGadget,
+};
+use ark_relations::r1cs::{Namespace, SynthesisError};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use core::{borrow::Borrow, fmt};
+
+/// The default number of decimal digits of an `Amount`, which is what `Display` and the binaries
+/// use. 2 means amounts are counted in cents, so $5.43 is stored as 543.
+///
+/// The precision is only a matter of parsing and printing. The circuits and commitments only ever
+/// see whole minor units, so the same proving key works for a currency with 0 or 3 decimals. Pass
+/// the currency's precision to `from_decimal_str` and `display` to use one.
+pub const AMOUNT_DECIMALS: u32 = 2;
+
+//
+// NATIVE IMPLEMENTATIONS
+//
+
+/// A fixed-point amount of money. It is stored as a whole number of minor units, e.g., cents, so
+/// all the integer arithmetic the circuits do on amounts is exact. How many minor units make a
+/// major unit is up to the currency, see `AMOUNT_DECIMALS`.
+///
+/// This serializes exactly like the underlying field element, which is also how `AmountVar`
+/// serializes. So commitments computed natively and in the circuit agree.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    CanonicalSerialize,
+    CanonicalDeserialize,
+)]
+pub struct Amount(
+    /// The amount in minor units
+    pub F,
+);
+
+impl Amount {
+    /// The number of minor units in one major unit with the given number of decimals, e.g., 100
+    /// cents in a dollar. Returns `None` if it doesn't fit in a `u128`.
+    pub fn scale(decimals: u32) -> Option<u128> {
+        10u128.checked_pow(decimals)
+    }
+
+    /// Makes an amount out of a whole number of minor units, e.g., `from_minor_units(543)` is
+    /// $5.43
+    pub fn from_minor_units(units: u64) -> Self {
+        Amount(F::from(units))
+    }
+
+    /// Returns the amount in minor units as an integer, or `None` if it doesn't fit in a `u128`
+    pub fn to_minor_units(&self) -> Option<u128> {
+        field_to_u128(&self.0)
+    }
+
+    /// Parses a decimal string like "5.43" or "5" in a currency with the given number of decimals.
+    /// Returns `None` if the string isn't a non-negative decimal number, or if it has more than
+    /// `decimals` decimal digits.
+    pub fn from_decimal_str(s: &str, decimals: u32) -> Option<Self> {
+        let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(frac) {
+            return None;
+        }
+        if frac.len() > decimals as usize {
+            return None;
+        }
+
+        // Pad the fractional part with zeros, so "5.4" is 540 cents
+        let padded_frac = format!("{frac:0<width$}", width = decimals as usize);
+        let whole: u128 = whole.parse().ok()?;
+        let frac: u128 = if padded_frac.is_empty() { 0 } else { padded_frac.parse().ok()? };
+        Some(Amount(F::from(whole.checked_mul(Self::scale(decimals)?)?.checked_add(frac)?)))
+    }
+
+    /// Returns a printable version of the amount in a currency with the given number of decimals
+    pub fn display(&self, decimals: u32) -> AmountDisplay {
+        AmountDisplay { amount: *self, decimals }
+    }
+}
+
+/// Prints the amount with `AMOUNT_DECIMALS` decimals, e.g., "5.43"
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(AMOUNT_DECIMALS).fmt(f)
+    }
+}
+
+/// An amount along with the precision to print it with. See `Amount::display`.
+pub struct AmountDisplay {
+    amount: Amount,
+    decimals: u32,
+}
+
+/// Prints the amount as a decimal number, e.g., "5.43". Amounts too large to be a small integer
+/// are printed as the raw field element.
+impl fmt::Display for AmountDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units = self.amount.to_minor_units();
+        match (units, Amount::scale(self.decimals)) {
+            (Some(units), Some(1)) => write!(f, "{units}"),
+            (Some(units), Some(scale)) => write!(
+                f,
+                "{}.{:0width$}",
+                units / scale,
+                units % scale,
+                width = self.decimals as usize
+            ),
+            _ => write!(f, "{}", self.amount.0),
+        }
+    }
+}
+
+/// An amount is a single public input, in minor units
+impl ToConstraintField<F> for Amount {
+    fn to_field_elements(&self) -> Option<Vec<F>> {
+        Some(vec![self.0])
+    }
+}
+
+/// A public interval `[min, max]` of amounts, e.g., for claiming a figure is between $4 and $6
+/// without revealing it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct AmountInterval {
+    pub min: Amount,
+    pub max: Amount,
+}
+
+impl AmountInterval {
+    /// Returns whether the amount is within the interval, including its ends
+    pub fn contains(&self, amount: &Amount) -> bool {
+        self.min <= *amount && *amount <= self.max
+    }
+}
+
+/// An interval is two public inputs, the minimum then the maximum
+impl ToConstraintField<F> for AmountInterval {
+    fn to_field_elements(&self) -> Option<Vec<F>> {
+        Some(vec![self.min.0, self.max.0])
+    }
+}
+
+//
+// R1CS IMPLEMENTATIONS
+//
+
+/// R1CS representation of Amount. Like `Amount`, it holds the amount in minor units.
+#[derive(Clone)]
+pub struct AmountVar(pub FV);
+
+impl AllocVar<Amount, F> for AmountVar {
+    fn new_variable<T: Borrow<Amount>>(
+        cs: impl Into<Namespace<F>>,
+        f: impl FnOnce() -> Result<T, SynthesisError>,
+        mode: AllocationMode,
+    ) -> Result<Self, SynthesisError> {
+        FV::new_variable(cs, || f().map(|amount| amount.borrow().0), mode).map(AmountVar)
+    }
+}
+
+impl R1CSVar<F> for AmountVar {
+    type Value = Amount;
+
+    fn cs(&self) -> ark_relations::r1cs::ConstraintSystemRef<F> {
+        self.0.cs()
+    }
+
+    fn value(&self) -> Result<Amount, SynthesisError> {
+        self.0.value().map(Amount)
+    }
+}
+
+/// Defines a way to serialize an AmountVar to bytes. This is only works if it is identical to the
+/// `impl CanonicalSerialize for Amount` serialization.
+impl ToBytesGadget<F> for AmountVar {
+    fn to_bytes(&self) -> Result<Vec<UInt8<F>>, SynthesisError> {
+        self.0.to_bytes()
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Parsing and printing round-trip, and anything that isn't a fixed-point number with at most
+    // the currency's number of decimals is rejected
+    #[test]
+    fn amount_parsing() {
+        for (s, units, printed) in [("5.43", 543, "5.43"), ("5.4", 540, "5.40"), ("5", 500, "5.00")]
+        {
+            let amount = Amount::from_decimal_str(s, AMOUNT_DECIMALS).unwrap();
+            assert_eq!(amount, Amount::from_minor_units(units));
+            assert_eq!(amount.to_string(), printed);
+        }
+
+        for bad in ["", ".5", "5.431", "-5", "5.4x", "five"] {
+            assert_eq!(Amount::from_decimal_str(bad, AMOUNT_DECIMALS), None, "parsed {bad:?}");
+        }
+    }
+
+    // Other currencies have other precisions, e.g., yen have no decimals and dinars have 3
+    #[test]
+    fn amount_precision() {
+        for (s, decimals, units, printed) in
+            [("543", 0, 543, "543"), ("5.4", 3, 5400, "5.400"), ("0.001", 3, 1, "0.001")]
+        {
+            let amount = Amount::from_decimal_str(s, decimals).unwrap();
+            assert_eq!(amount, Amount::from_minor_units(units));
+            assert_eq!(amount.display(decimals).to_string(), printed);
+        }
+
+        assert_eq!(Amount::from_decimal_str("5.4", 0), None);
+        assert_eq!(Amount::from_decimal_str("5", 39), None);
+    }
+
+    // The native and R1CS serializations of an amount must be identical, otherwise commitments
+    // wouldn't open in the circuit
+    #[test]
+    fn amount_serialization_matches() {
+        let amount = Amount::from_decimal_str("1234.56", AMOUNT_DECIMALS).unwrap();
+        let mut native_bytes = Vec::new();
+        amount.serialize_uncompressed(&mut native_bytes).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        let amount_var = AmountVar::new_witness(cs, || Ok(amount)).unwrap();
+        let var_bytes: Vec<u8> = amount_var.to_bytes().unwrap().value().unwrap();
+
+        assert_eq!(native_bytes, var_bytes);
+    }
+}
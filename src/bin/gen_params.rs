@@ -1,18 +1,23 @@
 use arkworks_merkle_tree_example::{
-    constraints::AnalysisCircuit,
+    amount::{Amount, AmountInterval},
+    constraints::{AnalysisCircuit, BoundsCommitment, Period, PresenceMask},
+    econ_data::even_partition,
+    growth::GrowthCircuit,
     hash::{LeafHash, TwoToOneHash},
-    merkle::{Leaf},
+    merkle::Leaf,
     util::{
-        write_to_file, PEDERSEN_PARAMS_FILENAME, POSSESSION_PK_FILENAME,
-        POSSESSION_VK_FILENAME,
+        gen_test_tree, write_to_file, AVG_RANGE_ONLY, GROWTH_PK_FILENAME, GROWTH_VK_FILENAME,
+        HIDDEN_BOUNDS, LEDGER_MODE, MISSING_PERIODS, NUM_GROUPS, NUM_PERIODS,
+        PEDERSEN_PARAMS_FILENAME, POSSESSION_PK_FILENAME, POSSESSION_VK_FILENAME, PUBLIC_PRESENCE,
+        REVEAL_MIN_MAX, ROUNDING,
     },
     E, F,
 };
 
-
 use ark_crypto_primitives::crh::{CRHScheme, TwoToOneCRHScheme};
 use ark_ff::UniformRand;
 use ark_groth16::{generate_random_parameters, prepare_verifying_key, ProvingKey};
+use ark_serialize::CanonicalSerialize;
 
 fn main() {
     // Use a deterministic RNG
@@ -41,69 +46,63 @@ fn main() {
     // Make a uniform leaf
     let zero_leaf: Leaf = [0u8; 64];
 
-    let claimed_avg = F::from(1200u32);
+    // Every group gets its own subtotal
+    let partition =
+        even_partition(NUM_PERIODS, NUM_GROUPS).expect("NUM_GROUPS must divide NUM_PERIODS");
+    let claimed_subtotal = Amount::from_minor_units(1200);
+
+    // In ledger mode, the circuit also needs a root and paths of the right height. Any tree of the
+    // same size as the ledger will do.
+    let tree = gen_test_tree(&leaf_crh_params, &two_to_one_crh_params);
+
+    // This is also the ledger that prove and verify use, so print its root for their command lines
+    let mut root_bytes = Vec::new();
+    tree.root().serialize_compressed(&mut root_bytes).unwrap();
+    println!("Merkle root: {}", zbase32::encode_full_bytes(&root_bytes));
+
+    // Every period gets the same placeholder values
+    let periods: Vec<Period> = (0..NUM_PERIODS)
+        .map(|_| Period {
+            com: zero_leaf.to_vec(),
+            data_com_rand: F::rand(&mut rng), // Another field elememnt
+            data_purchase_price: Amount::from_minor_units(543), // An amount in cents
+            data_quantity: F::from(1), // Another field element
+            path: Some(tree.generate_proof(0).unwrap()), // A path of the right height
+        })
+        .collect();
 
     // Now construct the circuit with all the random values
     let circuit = AnalysisCircuit {
         // Constants that the circuit needs
-        leaf_crh_params,
-        two_to_one_crh_params,
-
-        // Public inputs to the circuit
-        com_jan: zero_leaf.to_vec(),
-        com_feb: zero_leaf.to_vec(),
-        com_mar: zero_leaf.to_vec(),
-
-        com_apr: zero_leaf.to_vec(),
-        com_may: zero_leaf.to_vec(),
-        com_jun: zero_leaf.to_vec(),
-
-        com_jul: zero_leaf.to_vec(),
-        com_aug: zero_leaf.to_vec(),
-        com_sep: zero_leaf.to_vec(),
-
-        com_oct: zero_leaf.to_vec(),
-        com_nov: zero_leaf.to_vec(),
-        com_dec: zero_leaf.to_vec(),
-
-        // Commitment opening details
-        data_com_rand_jan: F::rand(&mut rng),       // Another field elememnt
-        data_com_rand_feb: F::rand(&mut rng),       // Another field elememnt
-        data_com_rand_mar: F::rand(&mut rng),       // Another field elememnt
-
-        data_com_rand_apr: F::rand(&mut rng),       // Another field elememnt
-        data_com_rand_may: F::rand(&mut rng),       // Another field elememnt
-        data_com_rand_jun: F::rand(&mut rng),       // Another field elememnt
-
-        data_com_rand_jul: F::rand(&mut rng),       // Another field elememnt
-        data_com_rand_aug: F::rand(&mut rng),       // Another field elememnt
-        data_com_rand_sep: F::rand(&mut rng),       // Another field elememnt
-
-        data_com_rand_oct: F::rand(&mut rng),       // Another field elememnt
-        data_com_rand_nov: F::rand(&mut rng),       // Another field elememnt
-        data_com_rand_dec: F::rand(&mut rng),       // Another field elememnt
-
-        data_purchase_price_jan: F::from(543), // Another field element
-        data_purchase_price_feb: F::from(543), // Another field element
-        data_purchase_price_mar: F::from(543), // Another field element
-
-        data_purchase_price_apr: F::from(543), // Another field element
-        data_purchase_price_may: F::from(543), // Another field element
-        data_purchase_price_jun: F::from(543), // Another field element
-
-        data_purchase_price_jul: F::from(543), // Another field element
-        data_purchase_price_aug: F::from(543), // Another field element
-        data_purchase_price_sep: F::from(543), // Another field element
-
-        data_purchase_price_oct: F::from(543), // Another field element
-        data_purchase_price_nov: F::from(543), // Another field element
-        data_purchase_price_dec: F::from(543), // Another field element
-
-        output_purchase_price_avg: claimed_avg,         // the output value
-
-        bounds_purchase_price_min: F::from(0),          // the minimum bounds
-        bounds_purchase_price_max: F::from(1000),       // the maximum bounds
-
+        leaf_crh_params: leaf_crh_params.clone(),
+        two_to_one_crh_params: two_to_one_crh_params.clone(),
+
+        // Public inputs and witnesses for every period
+        periods: periods.clone(),
+        ledger_root: LEDGER_MODE.then(|| tree.root()),
+        presence: MISSING_PERIODS.then(|| PresenceMask {
+            present: vec![true; NUM_PERIODS],
+            is_public: PUBLIC_PRESENCE,
+        }),
+
+        partition,
+        rounding: ROUNDING,
+
+        output_purchase_price_subtotals: vec![claimed_subtotal; NUM_GROUPS],   // the output values
+        output_purchase_price_total: claimed_subtotal,
+        output_purchase_price_avg_range: AVG_RANGE_ONLY.then(|| AmountInterval {
+            min: Amount::from_minor_units(0),
+            max: Amount::from_minor_units(1000),
+        }),
+        output_purchase_price_min: REVEAL_MIN_MAX.then(|| Amount::from_minor_units(543)),
+        output_purchase_price_max: REVEAL_MIN_MAX.then(|| Amount::from_minor_units(543)),
+
+        bounds_purchase_price_min: Amount::from_minor_units(0),          // the minimum bounds
+        bounds_purchase_price_max: Amount::from_minor_units(1000),       // the maximum bounds
+        bounds_com: HIDDEN_BOUNDS.then(|| BoundsCommitment {
+            com: zero_leaf.to_vec(),
+            com_rand: F::rand(&mut rng),
+        }),
     };
 
     // Generate the Groth16 proving and verifying key and write to files
@@ -113,4 +112,30 @@ fn main() {
     write_to_file(POSSESSION_VK_FILENAME, &vk);
     println!("Wrote {POSSESSION_PK_FILENAME}");
     println!("Wrote {POSSESSION_VK_FILENAME}");
+
+    //
+    // Do the same for the year-over-year GrowthCircuit. Both years have NUM_PERIODS periods, and
+    // their commitments are always public, so the placeholder paths are unused.
+    //
+
+    let growth_circuit = GrowthCircuit {
+        leaf_crh_params,
+        two_to_one_crh_params,
+
+        base_periods: periods.clone(),
+        current_periods: periods,
+
+        bounds_growth_percent_min: F::from(100),    // the claimed growth range
+        bounds_growth_percent_max: F::from(100),
+
+        bounds_purchase_price_min: Amount::from_minor_units(0),      // the minimum bounds
+        bounds_purchase_price_max: Amount::from_minor_units(1000),   // the maximum bounds
+    };
+
+    let pk: ProvingKey<E> = generate_random_parameters(growth_circuit, &mut rng).unwrap();
+    let vk = prepare_verifying_key(&pk.vk);
+    write_to_file(GROWTH_PK_FILENAME, &pk);
+    write_to_file(GROWTH_VK_FILENAME, &vk);
+    println!("Wrote {GROWTH_PK_FILENAME}");
+    println!("Wrote {GROWTH_VK_FILENAME}");
 }
@@ -1,16 +1,20 @@
 use arkworks_merkle_tree_example::{
-    constraints::AnalysisCircuit,
+    amount::{Amount, AmountInterval, AMOUNT_DECIMALS},
+    constraints::{AnalysisCircuit, BoundsCommitment, Period, PresenceMask},
+    econ_data::{even_partition, order_statistic, subtotals, OrderStatistic, PriceBounds},
     merkle::MerkleRoot,
     util::{
-        gen_test_tree, get_test_data, get_test_leaf, read_from_file, write_to_file,
-        POSSESSION_PROOF_FILENAME, POSSESSION_REVEALED_SERIAL_FILENAME, POSSESSION_VK_FILENAME,
+        gen_test_tree, get_test_data, get_test_leaf, read_from_file, write_to_file, AVG_RANGE_ONLY,
+        HIDDEN_BOUNDS, LEDGER_MODE, MISSING_PERIODS, NUM_GROUPS, NUM_PERIODS,
+        POSSESSION_COMMITMENTS_FILENAME, POSSESSION_OUTPUTS_FILENAME, POSSESSION_PROOF_FILENAME,
+        POSSESSION_VK_FILENAME, PUBLIC_PRESENCE, REVEAL_MIN_MAX, ROUNDING,
     },
-    E, F, 
+    E, F,
 };
 
 use std::env;
 
-use ark_ff::ToConstraintField;
+use ark_ff::{ToConstraintField, UniformRand};
 use ark_groth16::{create_random_proof, verify_proof, ProvingKey};
 use ark_serialize::CanonicalDeserialize;
 
@@ -18,17 +22,24 @@ const HELP_STR: &str = "\
 Error: bad command line arguments
 
 Usage:
-    cargo run --release --bin prove -- PEDERSEN_PARAM_FILE PROVING_KEY_FILE MERKLE_ROOT
+    cargo run --release --bin prove -- PEDERSEN_PARAM_FILE PROVING_KEY_FILE MERKLE_ROOT \\
+        [AVG_MIN AVG_MAX]
 Example:
     cargo run --release --bin prove -- \\
         pedersen_params.bin \\
         possession_proving_key.bin \\
-        f5pj64oh3m6anguhjb5rhfugwe44ximao17ya3wgx1fbmg1iobmo
+        $MERKLE_ROOT
+
+MERKLE_ROOT is the root that gen_params prints. It depends on the Pedersen params, so it changes
+whenever they're regenerated.
+
+If the proving key was generated with AVG_RANGE_ONLY, the average stays private, and AVG_MIN and
+AVG_MAX are the public interval it's proven to be in, e.g., 4.00 6.00.
 ";
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 4 {
+    if args.len() != if AVG_RANGE_ONLY { 6 } else { 4 } {
         println!("{}", HELP_STR);
         panic!("bad command line input");
     }
@@ -41,6 +52,12 @@ fn main() {
         MerkleRoot::deserialize_compressed(bytes.as_slice())
             .expect("Merkle root string is an invalid hash")
     };
+    let avg_range = AVG_RANGE_ONLY.then(|| {
+        let parse = |s: &str| {
+            Amount::from_decimal_str(s, AMOUNT_DECIMALS).expect("could not parse the interval")
+        };
+        AmountInterval { min: parse(&args[4]), max: parse(&args[5]) }
+    });
 
     //
     // Setup
@@ -50,9 +67,9 @@ fn main() {
 
     println!("Reading params and proving key...");
     // Read the hashing params from a file
-    let (leaf_crh_params, two_to_one_crh_params) = read_from_file(&pedersen_params_filename);
+    let (leaf_crh_params, two_to_one_crh_params) = read_from_file(pedersen_params_filename);
     // Read the Groth16 CRS from a file
-    let pk: ProvingKey<E> = read_from_file(&possession_pk_filename);
+    let pk: ProvingKey<E> = read_from_file(possession_pk_filename);
 
     // Generate a test tree and compute its root
     let tree = gen_test_tree(&leaf_crh_params, &two_to_one_crh_params);
@@ -74,69 +91,64 @@ fn main() {
     let idx_to_prove = our_idx;
     let claimed_leaf = &get_test_leaf(&leaf_crh_params, idx_to_prove);
 
-    let claimed_avg = data.purchase_price*F::from(12u32)/F::from(12u32);
+    // Every period opens the same data commitment
+    let periods: Vec<Period> = (0..NUM_PERIODS)
+        .map(|_| Period {
+            com: claimed_leaf.to_vec(),
+            data_com_rand,                                // The data's nonce
+            data_purchase_price: data.purchase_price,     // The data's purchase price
+            data_quantity: data.quantity,                 // The data's quantity
+            path: Some(tree.generate_proof(idx_to_prove).unwrap()), // The path to the ledger root
+        })
+        .collect();
+
+    // Compute the subtotal of every group, e.g., every quarter, and the grand total
+    let prices: Vec<Amount> = periods.iter().map(|period| period.data_purchase_price).collect();
+    let partition =
+        even_partition(NUM_PERIODS, NUM_GROUPS).expect("NUM_GROUPS must divide NUM_PERIODS");
+    let claimed_subtotals = subtotals(&prices, &partition).expect("could not sum the groups");
+    let claimed_total = Amount(claimed_subtotals.iter().map(|subtotal| subtotal.0).sum());
+    let claimed_min = order_statistic(&prices, OrderStatistic::Kth(0));
+    let claimed_max = order_statistic(&prices, OrderStatistic::Kth(prices.len() - 1));
+
+    // The bounds the prices must be within. If they're a confidential policy, we commit to them,
+    // and only the commitment is published.
+    let bounds = PriceBounds {
+        purchase_price_min: Amount::from_minor_units(0),
+        purchase_price_max: Amount::from_minor_units(1000),
+    };
+    let bounds_com = HIDDEN_BOUNDS.then(|| {
+        let com_rand = F::rand(&mut rng);
+        BoundsCommitment { com: bounds.commit(&leaf_crh_params, &com_rand).to_vec(), com_rand }
+    });
 
-    // We now have everything we need to build the PossessionCircuit
+    // We now have everything we need to build the AnalysisCircuit
     let circuit = AnalysisCircuit {
         // Constants that the circuit needs
         leaf_crh_params,
         two_to_one_crh_params,
 
-        // Public inputs to the circuit
-        com_jan: claimed_leaf.to_vec(),
-        com_feb: claimed_leaf.to_vec(),
-        com_mar: claimed_leaf.to_vec(),
-
-        com_apr: claimed_leaf.to_vec(),
-        com_may: claimed_leaf.to_vec(),
-        com_jun: claimed_leaf.to_vec(),
-
-        com_jul: claimed_leaf.to_vec(),
-        com_aug: claimed_leaf.to_vec(),
-        com_sep: claimed_leaf.to_vec(),
-
-        com_oct: claimed_leaf.to_vec(),
-        com_nov: claimed_leaf.to_vec(),
-        com_dec: claimed_leaf.to_vec(),
-
-        // Witness to membership
-        // Commitment opening details
-        data_com_rand_jan: data_com_rand,       // The data's nonce
-        data_com_rand_feb: data_com_rand,       // The data's nonce
-        data_com_rand_mar: data_com_rand,       // The data's nonce
-
-        data_com_rand_apr: data_com_rand,       // The data's nonce
-        data_com_rand_may: data_com_rand,       // The data's nonce
-        data_com_rand_jun: data_com_rand,       // The data's nonce
-
-        data_com_rand_jul: data_com_rand,       // The data's nonce
-        data_com_rand_aug: data_com_rand,       // The data's nonce
-        data_com_rand_sep: data_com_rand,       // The data's nonce
-
-        data_com_rand_oct: data_com_rand,       // The data's nonce
-        data_com_rand_nov: data_com_rand,       // The data's nonce
-        data_com_rand_dec: data_com_rand,       // The data's nonce
-
-        data_purchase_price_jan: data.purchase_price, // The datas' purchase price
-        data_purchase_price_feb: data.purchase_price, // The datas' purchase price
-        data_purchase_price_mar: data.purchase_price, // The datas' purchase price
-
-        data_purchase_price_apr: data.purchase_price, // The datas' purchase price
-        data_purchase_price_may: data.purchase_price, // The datas' purchase price
-        data_purchase_price_jun: data.purchase_price, // The datas' purchase price
-
-        data_purchase_price_jul: data.purchase_price, // The datas' purchase price
-        data_purchase_price_aug: data.purchase_price, // The datas' purchase price
-        data_purchase_price_sep: data.purchase_price, // The datas' purchase price
-
-        data_purchase_price_oct: data.purchase_price, // The datas' purchase price
-        data_purchase_price_nov: data.purchase_price, // The datas' purchase price
-        data_purchase_price_dec: data.purchase_price, // The datas' purchase price
-
-        output_purchase_price_avg: claimed_avg,         // the output value
-
-        bounds_purchase_price_min: F::from(0),          // the minimum bounds
-        bounds_purchase_price_max: F::from(1000),       // the maximum bounds
+        // Public inputs and witnesses for every period
+        periods,
+        ledger_root: LEDGER_MODE.then_some(root),
+        // We have data for every period
+        presence: MISSING_PERIODS.then(|| PresenceMask {
+            present: vec![true; NUM_PERIODS],
+            is_public: PUBLIC_PRESENCE,
+        }),
+
+        partition,
+        rounding: ROUNDING,
+
+        output_purchase_price_subtotals: claimed_subtotals,     // the output values
+        output_purchase_price_total: claimed_total,
+        output_purchase_price_avg_range: avg_range,
+        output_purchase_price_min: claimed_min.filter(|_| REVEAL_MIN_MAX),
+        output_purchase_price_max: claimed_max.filter(|_| REVEAL_MIN_MAX),
+
+        bounds_purchase_price_min: bounds.purchase_price_min,   // the minimum bounds
+        bounds_purchase_price_max: bounds.purchase_price_max,   // the maximum bounds
+        bounds_com,
     };
 
     // Create the proof
@@ -149,17 +161,26 @@ fn main() {
 
     // Verify the proof package. This should succeed
     let vk = read_from_file(POSSESSION_VK_FILENAME);
-    let public_inputs = [
-        root.to_field_elements().unwrap(),
-    ]
-    .concat();
+    let statement = circuit.statement();
+    let public_inputs = statement.to_field_elements().unwrap();
     assert!(
         verify_proof(&vk, &proof, &public_inputs).unwrap(),
         "honest proof failed to verify with supplied verifying key"
     );
 
-    // Write the proof and serial to a file
+    // Write the proof, the commitments, and the outputs to files. In ledger mode the commitments
+    // stay private, and the verifier uses the ledger root instead.
     write_to_file(POSSESSION_PROOF_FILENAME, &proof);
     println!("Wrote {POSSESSION_PROOF_FILENAME}");
-    println!("Wrote {POSSESSION_REVEALED_SERIAL_FILENAME}");
+    if !LEDGER_MODE {
+        write_to_file(POSSESSION_COMMITMENTS_FILENAME, &statement.coms);
+        println!("Wrote {POSSESSION_COMMITMENTS_FILENAME}");
+    }
+    write_to_file(POSSESSION_OUTPUTS_FILENAME, &statement.outputs);
+    println!("Wrote {POSSESSION_OUTPUTS_FILENAME}");
+
+    // The policy owner publishes this, so verifiers can check the proof used their policy
+    if let Some(bounds_com) = &statement.outputs.bounds_com {
+        println!("Policy commitment: {}", zbase32::encode_full_bytes(bounds_com));
+    }
 }
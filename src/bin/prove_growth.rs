@@ -0,0 +1,113 @@
+use arkworks_merkle_tree_example::{
+    amount::Amount,
+    econ_data::growth_percent,
+    growth::GrowthCircuit,
+    util::{
+        gen_test_periods, read_from_file, write_to_file, GROWTH_BASE_COMMITMENTS_FILENAME,
+        GROWTH_CURRENT_COMMITMENTS_FILENAME, GROWTH_OUTPUTS_FILENAME, GROWTH_PROOF_FILENAME,
+        GROWTH_VK_FILENAME, NUM_PERIODS,
+    },
+    E, F,
+};
+
+use std::env;
+
+use ark_ff::ToConstraintField;
+use ark_groth16::{create_random_proof, verify_proof, ProvingKey};
+
+const HELP_STR: &str = "\
+Error: bad command line arguments
+
+Usage:
+    cargo run --release --bin prove_growth -- PEDERSEN_PARAM_FILE PROVING_KEY_FILE
+Example:
+    cargo run --release --bin prove_growth -- \\
+        pedersen_params.bin \\
+        growth_proving_key.bin
+";
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        println!("{}", HELP_STR);
+        panic!("bad command line input");
+    }
+    // Unpack command line args
+    let pedersen_params_filename = &args[1];
+    let growth_pk_filename = &args[2];
+
+    //
+    // Setup
+    //
+
+    let mut rng = rand::thread_rng();
+
+    println!("Reading params and proving key...");
+    // Read the hashing params from a file
+    let (leaf_crh_params, two_to_one_crh_params) = read_from_file(pedersen_params_filename);
+    // Read the Groth16 CRS from a file
+    let pk: ProvingKey<E> = read_from_file(growth_pk_filename);
+
+    // Imagine we bought something every month of last year and of this year, and committed to
+    // every purchase. Last year's prices hover around 500, and this year's around 530.
+    let base_prices: Vec<Amount> = (0..NUM_PERIODS)
+        .map(|i| Amount::from_minor_units(490 + 2 * i as u64))
+        .collect();
+    let current_prices: Vec<Amount> = (0..NUM_PERIODS)
+        .map(|i| Amount::from_minor_units(520 + 2 * i as u64))
+        .collect();
+    let base_periods = gen_test_periods(&leaf_crh_params, &base_prices, &mut rng);
+    let current_periods = gen_test_periods(&leaf_crh_params, &current_prices, &mut rng);
+
+    //
+    // Now generate a proof
+    //
+
+    // Claim the growth to the nearest percent below and above
+    let growth = growth_percent(&base_prices, &current_prices)
+        .expect("could not compute the growth of the purchase prices");
+
+    // We now have everything we need to build the GrowthCircuit
+    let circuit = GrowthCircuit {
+        // Constants that the circuit needs
+        leaf_crh_params,
+        two_to_one_crh_params,
+
+        // Public inputs and witnesses for every period of both years
+        base_periods,
+        current_periods,
+
+        bounds_growth_percent_min: growth,              // the claimed growth range
+        bounds_growth_percent_max: growth + F::from(1),
+
+        bounds_purchase_price_min: Amount::from_minor_units(0),          // the minimum bounds
+        bounds_purchase_price_max: Amount::from_minor_units(1000),       // the maximum bounds
+    };
+
+    // Create the proof
+    println!("Proving...");
+    let proof = create_random_proof(circuit.clone(), &pk, &mut rng).unwrap();
+
+    //
+    // Wrap-up
+    //
+
+    // Verify the proof package. This should succeed
+    let vk = read_from_file(GROWTH_VK_FILENAME);
+    let statement = circuit.statement();
+    let public_inputs = statement.to_field_elements().unwrap();
+    assert!(
+        verify_proof(&vk, &proof, &public_inputs).unwrap(),
+        "honest proof failed to verify with supplied verifying key"
+    );
+
+    // Write the proof, both years' commitments, and the outputs to files
+    write_to_file(GROWTH_PROOF_FILENAME, &proof);
+    println!("Wrote {GROWTH_PROOF_FILENAME}");
+    write_to_file(GROWTH_BASE_COMMITMENTS_FILENAME, &statement.base_coms);
+    println!("Wrote {GROWTH_BASE_COMMITMENTS_FILENAME}");
+    write_to_file(GROWTH_CURRENT_COMMITMENTS_FILENAME, &statement.current_coms);
+    println!("Wrote {GROWTH_CURRENT_COMMITMENTS_FILENAME}");
+    write_to_file(GROWTH_OUTPUTS_FILENAME, &statement.outputs);
+    println!("Wrote {GROWTH_OUTPUTS_FILENAME}");
+}
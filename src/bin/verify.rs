@@ -1,4 +1,9 @@
-use arkworks_merkle_tree_example::{merkle::MerkleRoot, util::read_from_file, E, F};
+use arkworks_merkle_tree_example::{
+    constraints::{AnalysisOutputs, AnalysisStatement},
+    merkle::MerkleRoot,
+    util::read_from_file,
+    E, F,
+};
 
 use ark_ff::ToConstraintField;
 use ark_groth16::{verify_proof, PreparedVerifyingKey, Proof};
@@ -8,52 +13,84 @@ const HELP_STR: &str = "\
 Error: bad command line arguments
 
 Usage:
-    cargo run --release --bin verify -- VERIFYING_KEY_FILE PROOF_FILE PUBLIC_INPUTS_FILE MERKLE_ROOT
+    cargo run --release --bin verify -- VERIFYING_KEY_FILE PROOF_FILE OUTPUTS_FILE \\
+        (--root MERKLE_ROOT | --commitments COMMITMENTS_FILE) [--policy POLICY_COMMITMENT]
 Example:
     cargo run --release --bin verify -- \\
         possession_verifying_key.bin \\
         possession_proof.bin \\
-        possession_revealed_serial.bin \\
-        f5pj64oh3m6anguhjb5rhfugwe44ximao17ya3wgx1fbmg1iobmo
+        possession_outputs.bin \\
+        --root $MERKLE_ROOT
+
+MERKLE_ROOT is the root that gen_params prints, the same one given to prove.
+
+If the proving key was generated with HIDDEN_BOUNDS, the price bounds are a confidential policy,
+and POLICY_COMMITMENT is the commitment to it that the policy owner published.
 ";
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    if args.len() != 5 {
+    if args.len() != 6 && !(args.len() == 8 && args[6] == "--policy") {
         println!("{}", HELP_STR);
         panic!("bad command line input");
     }
     // Unpack command line args
     let possession_vk_filename = &args[1];
     let possession_proof_filename = &args[2];
-    let possession_revealed_serial_filename = &args[3];
-    let given_merkle_root = {
-        let bytes = zbase32::decode_full_bytes(args[4].as_bytes())
-            .expect("could not decode Merkle root string");
-        MerkleRoot::deserialize_compressed(bytes.as_slice())
-            .expect("Merkle root string is an invalid hash")
+    let possession_outputs_filename = &args[3];
+
+    // We either know the root of the ledger the data came from, or the data commitments themselves
+    let (ledger_root, coms): (Option<MerkleRoot>, Vec<Vec<u8>>) = match args[4].as_str() {
+        "--root" => {
+            let bytes = zbase32::decode_full_bytes(args[5].as_bytes())
+                .expect("could not decode Merkle root string");
+            let root = MerkleRoot::deserialize_compressed(bytes.as_slice())
+                .expect("Merkle root string is an invalid hash");
+            (Some(root), Vec::new())
+        }
+        "--commitments" => (None, read_from_file(&args[5])),
+        _ => {
+            println!("{}", HELP_STR);
+            panic!("bad command line input");
+        }
     };
 
+    // The policy commitment we trust, if the bounds are confidential
+    let policy_com: Option<Vec<u8>> = args.get(7).map(|com| {
+        zbase32::decode_full_bytes(com.as_bytes()).expect("could not decode policy commitment")
+    });
+
     //
     // Setup
     //
 
     println!("Reading verifying key, proof, and public inputs...");
-    // Read the Groth16 CRS, proof, and serial from a file
+    // Read the Groth16 CRS, proof, and claimed outputs from a file
     let vk: PreparedVerifyingKey<E> = read_from_file(possession_vk_filename);
     let proof: Proof<E> = read_from_file(possession_proof_filename);
-    let card_serial: F = read_from_file(possession_revealed_serial_filename);
+    let outputs: AnalysisOutputs = read_from_file(possession_outputs_filename);
+
+    // The bounds commitment in the outputs comes from the prover. It only means something if it's
+    // the commitment to the policy we expect.
+    assert_eq!(
+        outputs.bounds_com, policy_com,
+        "the proof's bounds commitment is not the given policy commitment"
+    );
 
     //
-    // Compute the public inputs for the circuit. We know the Merkle root, and we were given the
-    // card serial
+    // Compute the public inputs for the circuit. We know the ledger root or the data commitments
+    // for every period, and we were given the claimed outputs and the bounds they were computed
+    // under
     //
 
     // Serialize everything to field elements
-    let public_inputs: Vec<_> = [
-        given_merkle_root.to_field_elements().unwrap(),
-        card_serial.to_field_elements().unwrap(),
-    ].concat(); // Do the same thing that was done in prove.rs
+    let statement = AnalysisStatement {
+        ledger_root,
+        coms,
+        outputs: outputs.clone(),
+    };
+    // Do the same thing that was done in prove.rs
+    let public_inputs: Vec<F> = statement.to_field_elements().unwrap();
 
     //
     // Verify the proof
@@ -65,5 +102,34 @@ fn main() {
         "proof failed to verify"
     );
 
+    if let Some(presence) = &outputs.presence {
+        let missing: Vec<usize> = (1..=presence.len()).filter(|&i| !presence[i - 1]).collect();
+        println!("Missing periods: {missing:?}");
+    }
+    if let Some(num_present) = outputs.num_present {
+        println!("Periods with data: {num_present}");
+    }
+    for (i, subtotal) in outputs.output_purchase_price_subtotals.iter().enumerate() {
+        println!("Group {} purchase price subtotal: {subtotal}", i + 1);
+    }
+    if let Some(total) = outputs.output_purchase_price_total {
+        println!("Total purchase price: {total}");
+    }
+    if let Some(range) = outputs.output_purchase_price_avg_range {
+        println!("Average purchase price: between {} and {}", range.min, range.max);
+    }
+    if let Some(min) = outputs.output_purchase_price_min {
+        println!("Minimum purchase price: {min}");
+    }
+    if let Some(max) = outputs.output_purchase_price_max {
+        println!("Maximum purchase price: {max}");
+    }
+    match (outputs.bounds_purchase_price_min, outputs.bounds_purchase_price_max) {
+        (Some(min), Some(max)) => println!("Purchase price bounds: {min} to {max}"),
+        _ => println!(
+            "Purchase price bounds: hidden behind the policy commitment {}",
+            args[7]
+        ),
+    }
     println!("Proof verified successfully");
 }
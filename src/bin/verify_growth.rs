@@ -0,0 +1,78 @@
+use arkworks_merkle_tree_example::{
+    growth::{GrowthOutputs, GrowthStatement},
+    util::read_from_file,
+    E, F,
+};
+
+use ark_ff::ToConstraintField;
+use ark_groth16::{verify_proof, PreparedVerifyingKey, Proof};
+
+const HELP_STR: &str = "\
+Error: bad command line arguments
+
+Usage:
+    cargo run --release --bin verify_growth -- VERIFYING_KEY_FILE PROOF_FILE OUTPUTS_FILE \\
+        BASE_COMMITMENTS_FILE CURRENT_COMMITMENTS_FILE
+Example:
+    cargo run --release --bin verify_growth -- \\
+        growth_verifying_key.bin \\
+        growth_proof.bin \\
+        growth_outputs.bin \\
+        growth_base_commitments.bin \\
+        growth_current_commitments.bin
+";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 6 {
+        println!("{}", HELP_STR);
+        panic!("bad command line input");
+    }
+    // Unpack command line args
+    let growth_vk_filename = &args[1];
+    let growth_proof_filename = &args[2];
+    let growth_outputs_filename = &args[3];
+    let base_coms_filename = &args[4];
+    let current_coms_filename = &args[5];
+
+    //
+    // Setup
+    //
+
+    println!("Reading verifying key, proof, and public inputs...");
+    // Read the Groth16 CRS, proof, claimed outputs, and both years' commitments from files
+    let vk: PreparedVerifyingKey<E> = read_from_file(growth_vk_filename);
+    let proof: Proof<E> = read_from_file(growth_proof_filename);
+    let outputs: GrowthOutputs = read_from_file(growth_outputs_filename);
+    let base_coms: Vec<Vec<u8>> = read_from_file(base_coms_filename);
+    let current_coms: Vec<Vec<u8>> = read_from_file(current_coms_filename);
+
+    //
+    // Compute the public inputs for the circuit. We know the data commitments for every period of
+    // both years, and we were given the claimed growth range and the bounds it was computed under
+    //
+
+    // Serialize everything to field elements
+    let statement = GrowthStatement {
+        base_coms,
+        current_coms,
+        outputs: outputs.clone(),
+    };
+    // Do the same thing that was done in prove_growth.rs
+    let public_inputs: Vec<F> = statement.to_field_elements().unwrap();
+
+    //
+    // Verify the proof
+    //
+
+    assert!(
+        verify_proof(&vk, &proof, &public_inputs).unwrap(),
+        "proof failed to verify"
+    );
+
+    println!(
+        "Average purchase price is between {}% and {}% of last year's",
+        outputs.bounds_growth_percent_min, outputs.bounds_growth_percent_max
+    );
+    println!("Proof verified successfully");
+}
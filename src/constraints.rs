@@ -1,24 +1,92 @@
 use crate::{
-    econ_data::DataVar,
+    amount::{Amount, AmountInterval, AmountVar},
+    econ_data::{field_to_u128, DataVar, PriceBounds, PriceBoundsVar, RoundingMode},
     hash::{
         LeafHash, 
         LeafHashParamsVar, 
         TwoToOneHash, 
-        //TwoToOneHashParamsVar,
+        TwoToOneHashParamsVar,
     },
+    merkle::{MerkleRoot, RootVar, SimplePath, SimplePathVar},
+    range::{enforce_bit_width, enforce_cmp_small, VALUE_BITS},
     F, FV,
 };
 
 use ark_crypto_primitives::crh::{CRHScheme, TwoToOneCRHScheme};
-use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, uint8::UInt8};
+use ark_ff::ToConstraintField;
+use ark_r1cs_std::{
+    alloc::{AllocVar, AllocationMode}, boolean::Boolean, eq::EqGadget, fields::FieldVar, R1CSVar,
+    ToConstraintFieldGadget,
+};
 use ark_relations::{
     ns,
     r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
 };
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use core::cmp::Ordering;
 
+/// The committed data for a single period (e.g., one month) of the analysis
+#[derive(Clone)]
+pub struct Period {
+    // Public inputs to the circuit
+    /// The leaf in that tree. In our case, the leaf is also a commitment to the data we're showing.
+    /// It is packed into field elements when it's given to the circuit as a public input. In
+    /// ledger mode it stays private, and is only used to compute the path below.
+    pub com: Vec<u8>,
+
+    // Private inputs (aka "witnesses") for the circuit
+    /// The amount the data was purchased for
+    pub data_purchase_price: Amount,
+    /// The number of units that were purchased
+    pub data_quantity: F,
+    /// The private randomness used to commit to the data
+    pub data_com_rand: F,
+    /// The Merkle authentication path from the commitment to the ledger root. Only used in ledger
+    /// mode.
+    pub path: Option<SimplePath>,
+}
+
+/// A commitment to confidential price bounds, made with `PriceBounds::commit`. See
+/// `AnalysisCircuit::bounds_com`.
+#[derive(Clone)]
+pub struct BoundsCommitment {
+    /// The public commitment to the bounds. It is packed into field elements like a data
+    /// commitment.
+    pub com: Vec<u8>,
+    /// The private randomness used to commit to the bounds
+    pub com_rand: F,
+}
+
+/// Which periods have data. Real data has gaps, e.g., a store closed in August or a month wasn't
+/// reported, so absent periods need no commitment opening and drop out of every sum and count.
+/// See `AnalysisCircuit::presence`.
+#[derive(Clone)]
+pub struct PresenceMask {
+    /// Whether each period has data, in order
+    pub present: Vec<bool>,
+    /// Whether the mask is a public input. If it isn't, only the number of present periods is, and
+    /// nothing binds the mask to the data: the prover can mark any period absent, e.g., the
+    /// months with the highest prices, as long as the count matches.
+    pub is_public: bool,
+}
+
+impl PresenceMask {
+    /// The number of periods that have data
+    pub fn num_present(&self) -> u64 {
+        self.present.iter().filter(|&&present| present).count() as u64
+    }
+}
+
 /// Our ZK circuit. This is what we will create and pass to the Groth16 prover in order to do a ZK
 /// proof of possession
+///
+/// The number of periods, the partition and the rounding are constants in the constraints, and
+/// whether each optional field below is set changes which inputs there are. So all of them are
+/// fixed when the proving key is generated, and only the values of the inputs vary per proof.
+///
+/// With a private presence mask, the verifier learns that the claims hold over some set of
+/// periods of the revealed size, but not which one. The prover picks it, so a verifier who needs
+/// every period that has data to be counted must use a public mask.
 #[derive(Clone)]
 pub struct AnalysisCircuit {
     // These are constants that will be embedded into the circuit. They describe how the hash
@@ -26,261 +94,862 @@ pub struct AnalysisCircuit {
     pub leaf_crh_params: <LeafHash as CRHScheme>::Parameters,
     pub two_to_one_crh_params: <TwoToOneHash as TwoToOneCRHScheme>::Parameters,
 
-    // Public inputs to the circuit
-    /// The leaf in that tree. In our case, the leaf is also a commitment to the data we're showing
-    pub com_jan: Vec<u8>,
-    pub com_feb: Vec<u8>,
-    pub com_mar: Vec<u8>,
+    /// The data for every period being analyzed, e.g., 4 quarters, 12 months or 52 weeks
+    pub periods: Vec<Period>,
 
-    pub com_apr: Vec<u8>,
-    pub com_may: Vec<u8>,
-    pub com_jun: Vec<u8>,
+    /// The root of the public ledger. If this is set, the data commitments are kept private and
+    /// are instead proven to be leaves of the ledger, so the verifier learns that the records come
+    /// from the ledger but not which ones they are. Otherwise the commitments are public inputs.
+    pub ledger_root: Option<MerkleRoot>,
 
-    pub com_jul: Vec<u8>,
-    pub com_aug: Vec<u8>,
-    pub com_sep: Vec<u8>,
+    /// Which periods have data, if some may be missing. The `Period` of an absent period is a
+    /// placeholder whose commitment is never opened, though in ledger mode it still needs a path
+    /// of the right height. Without a mask every period is present. See `PresenceMask::is_public`
+    /// for what a private mask proves.
+    pub presence: Option<PresenceMask>,
 
-    pub com_oct: Vec<u8>,
-    pub com_nov: Vec<u8>,
-    pub com_dec: Vec<u8>,
+    /// The groups of periods that get their own subtotal, e.g., 4 quarters of 3 months each. Each
+    /// group is the list of its period indices, and every period must be in exactly one group.
+    /// Use `econ_data::even_partition` for groups of consecutive periods.
+    pub partition: Vec<Vec<usize>>,
 
-    // Private inputs (aka "witnesses") for the circuit
-    /// The amount the data was purchased for
-    pub data_purchase_price_jan: F,
-    pub data_purchase_price_feb: F,
-    pub data_purchase_price_mar: F,
-    
-    pub data_purchase_price_apr: F,
-    pub data_purchase_price_may: F,
-    pub data_purchase_price_jun: F,
-    
-    pub data_purchase_price_jul: F,
-    pub data_purchase_price_aug: F,
-    pub data_purchase_price_sep: F,
-    
-    pub data_purchase_price_oct: F,
-    pub data_purchase_price_nov: F,
-    pub data_purchase_price_dec: F,
-    
-    /// The private randomness used to commit to the data
-    pub data_com_rand_jan: F,
-    pub data_com_rand_feb: F,
-    pub data_com_rand_mar: F,
+    /// How the average is rounded in range-only mode. The average is over the present periods
+    /// only.
+    pub rounding: RoundingMode,
 
-    pub data_com_rand_apr: F,
-    pub data_com_rand_may: F,
-    pub data_com_rand_jun: F,
+    // The analysis output 
+    /// The sum of the purchase prices in each group of `partition`, in the same order. Absent
+    /// periods count as 0. Use `econ_data::subtotals` to compute them.
+    pub output_purchase_price_subtotals: Vec<Amount>,
+    /// The sum of all the purchase prices, i.e., of the subtotals
+    pub output_purchase_price_total: Amount,
+    /// The public interval the average is claimed to be in, if only that is revealed. If this is
+    /// set, the subtotals and the total are kept private, and the verifier learns only that the
+    /// average, rounded according to `rounding`, is within the interval.
+    pub output_purchase_price_avg_range: Option<AmountInterval>,
+    /// The smallest purchase price of the present periods, if it is revealed
+    pub output_purchase_price_min: Option<Amount>,
+    /// The largest purchase price of the present periods, if it is revealed
+    pub output_purchase_price_max: Option<Amount>,
+
+    // The analysis bounds. These are public inputs unless `bounds_com` is set.
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+
+    /// The commitment to the bounds, if they are a confidential policy. If this is set, the bounds
+    /// are kept private and opened against this commitment, so the verifier learns only the
+    /// commitment.
+    pub bounds_com: Option<BoundsCommitment>,
+}
 
-    pub data_com_rand_jul: F,
-    pub data_com_rand_aug: F,
-    pub data_com_rand_sep: F,
+/// The claimed outputs of an `AnalysisCircuit`, along with the bounds they were computed under.
+/// The prover publishes these next to the proof.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct AnalysisOutputs {
+    /// Which periods have data, if the presence mask is public
+    pub presence: Option<Vec<bool>>,
+    /// The number of periods that have data, if the presence mask is private
+    pub num_present: Option<u64>,
+    /// The subtotal of every group, unless only the interval of the average is revealed
+    pub output_purchase_price_subtotals: Vec<Amount>,
+    /// The grand total, unless only the interval of the average is revealed
+    pub output_purchase_price_total: Option<Amount>,
+    /// The interval the average is in, if the average itself is hidden
+    pub output_purchase_price_avg_range: Option<AmountInterval>,
+    pub output_purchase_price_min: Option<Amount>,
+    pub output_purchase_price_max: Option<Amount>,
+    /// The bounds, unless they are hidden
+    pub bounds_purchase_price_min: Option<Amount>,
+    pub bounds_purchase_price_max: Option<Amount>,
+    /// The commitment to the bounds, if they are hidden
+    pub bounds_com: Option<Vec<u8>>,
+}
 
-    pub data_com_rand_oct: F,
-    pub data_com_rand_nov: F,
-    pub data_com_rand_dec: F,
+/// Serializes the outputs in the same order the circuit allocates them. Outputs that aren't
+/// revealed are skipped, and so are the bounds if they are hidden behind a commitment.
+impl ToConstraintField<F> for AnalysisOutputs {
+    fn to_field_elements(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                match &self.presence {
+                    Some(presence) => presence.iter().map(|&present| F::from(present)).collect(),
+                    None => Vec::new(),
+                },
+                self.num_present.map(F::from).into_iter().collect(),
+                self.output_purchase_price_subtotals.iter().map(|subtotal| subtotal.0).collect(),
+                self.output_purchase_price_total.map(|total| total.0).into_iter().collect(),
+                match &self.output_purchase_price_avg_range {
+                    Some(range) => range.to_field_elements()?,
+                    None => Vec::new(),
+                },
+                self.output_purchase_price_min.map(|min| min.0).into_iter().collect(),
+                self.output_purchase_price_max.map(|max| max.0).into_iter().collect(),
+                self.bounds_purchase_price_min.map(|min| min.0).into_iter().collect(),
+                self.bounds_purchase_price_max.map(|max| max.0).into_iter().collect(),
+                match &self.bounds_com {
+                    Some(com) => com.to_field_elements()?,
+                    None => Vec::new(),
+                },
+            ]
+            .concat(),
+        )
+    }
+}
 
-    // The analysis output 
-    pub output_purchase_price_avg: F,
+/// The public statement proven by an `AnalysisCircuit`. The verifier builds this from the
+/// commitments it knows about and the claimed outputs, then converts it to the public-input vector.
+#[derive(Clone)]
+pub struct AnalysisStatement {
+    /// The root of the public ledger, if the circuit is in ledger mode
+    pub ledger_root: Option<MerkleRoot>,
+    /// The data commitment for every period, in order. This is empty in ledger mode.
+    pub coms: Vec<Vec<u8>>,
+    pub outputs: AnalysisOutputs,
+}
 
-    // The analysis bounds
-    pub bounds_purchase_price_min: F,
-    pub bounds_purchase_price_max: F,
+/// Serializes the public inputs that identify the committed data, in the same order that
+/// `open_periods` allocates them. This is the ledger root in ledger mode, and the commitments
+/// otherwise. Each commitment is packed into as few field elements as possible, rather than one
+/// per byte.
+pub fn data_public_inputs(ledger_root: Option<&MerkleRoot>, coms: &[Vec<u8>]) -> Option<Vec<F>> {
+    let packed_coms = coms
+        .iter()
+        .map(|com| com.to_field_elements())
+        .collect::<Option<Vec<_>>>()?;
+
+    let packed_root = match ledger_root {
+        Some(root) => root.to_field_elements()?,
+        None => Vec::new(),
+    };
+
+    Some([packed_root, packed_coms.concat()].concat())
 }
 
-/// generate_constraints is where the circuit functionality is defined. It doesn't return any
-/// value. Rather, it takes in a constraint system, and adds a bunch of constraints to that system
-/// (implicitly or explicitly). A proof is valid if and only if the final constraint system is
-/// satisfied.
-impl ConstraintSynthesizer<F> for AnalysisCircuit {
-    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
-        // First, allocate the public parameters as constants
-        let leaf_crh_params = LeafHashParamsVar::new_constant(cs.clone(), &self.leaf_crh_params)?;
-        //let two_to_one_crh_params =
-        //    TwoToOneHashParamsVar::new_constant(cs.clone(), &self.two_to_one_crh_params)?;
+/// Returns the commitments that are public inputs, i.e., none of them in ledger mode and all of
+/// them otherwise
+pub fn public_coms(ledger_root: Option<&MerkleRoot>, periods: &[Period]) -> Vec<Vec<u8>> {
+    match ledger_root {
+        Some(_) => Vec::new(),
+        None => periods.iter().map(|period| period.com.clone()).collect(),
+    }
+}
 
-        //
-        // Next, allocate the public inputs. Note the ns! macros are just to create name spaces for
-        // our constraints. It doesn't matter what this does, and it doesn't matter what string you
-        // give it.
-        //
+/// Serializes the statement in the same order the circuit allocates its public inputs
+impl ToConstraintField<F> for AnalysisStatement {
+    fn to_field_elements(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                data_public_inputs(self.ledger_root.as_ref(), &self.coms)?,
+                self.outputs.to_field_elements()?,
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl AnalysisCircuit {
+    /// Returns the public statement this circuit proves
+    pub fn statement(&self) -> AnalysisStatement {
+        AnalysisStatement {
+            ledger_root: self.ledger_root,
+            coms: public_coms(self.ledger_root.as_ref(), &self.periods),
+            outputs: AnalysisOutputs {
+                presence: self
+                    .presence
+                    .as_ref()
+                    .filter(|mask| mask.is_public)
+                    .map(|mask| mask.present.clone()),
+                num_present: self
+                    .presence
+                    .as_ref()
+                    .filter(|mask| !mask.is_public)
+                    .map(PresenceMask::num_present),
+                output_purchase_price_subtotals: match self.output_purchase_price_avg_range {
+                    Some(_) => Vec::new(),
+                    None => self.output_purchase_price_subtotals.clone(),
+                },
+                output_purchase_price_total: self
+                    .output_purchase_price_avg_range
+                    .is_none()
+                    .then_some(self.output_purchase_price_total),
+                output_purchase_price_avg_range: self.output_purchase_price_avg_range,
+                output_purchase_price_min: self.output_purchase_price_min,
+                output_purchase_price_max: self.output_purchase_price_max,
+                bounds_purchase_price_min: self
+                    .bounds_com
+                    .is_none()
+                    .then_some(self.bounds_purchase_price_min),
+                bounds_purchase_price_max: self
+                    .bounds_com
+                    .is_none()
+                    .then_some(self.bounds_purchase_price_max),
+                bounds_com: self.bounds_com.as_ref().map(|bounds_com| bounds_com.com.clone()),
+            },
+        }
+    }
+}
 
-        // Card commitment. This is also the leaf in our tree.
-        let claimed_data_com_var_jan = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_jan)?;
-        let claimed_data_com_var_feb = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_feb)?;
-        let claimed_data_com_var_mar = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_mar)?;
+/// A circuit whose public inputs can be computed natively, i.e., without running the circuit. This
+/// is what a verifier does, and what the tests check every circuit against.
+pub trait PublicInputs {
+    /// Returns the public inputs of this circuit, in the order the circuit allocates them
+    fn public_inputs(&self) -> Option<Vec<F>>;
+}
 
-        let claimed_data_com_var_apr = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_apr)?;
-        let claimed_data_com_var_may = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_may)?;
-        let claimed_data_com_var_jun = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_jun)?;
+impl PublicInputs for AnalysisCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        self.statement().to_field_elements()
+    }
+}
 
-        let claimed_data_com_var_jul = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_jul)?;
-        let claimed_data_com_var_aug = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_aug)?;
-        let claimed_data_com_var_sep = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_sep)?;
+/// The committed periods a circuit analyzes, along with everything needed to open them. The
+/// analysis circuits besides `AnalysisCircuit` hold one of these, and open it before anything
+/// else.
+#[derive(Clone)]
+pub struct CommittedPeriods {
+    // These are constants that will be embedded into the circuit. They describe how the hash
+    // function works. Don't worry about this.
+    pub leaf_crh_params: <LeafHash as CRHScheme>::Parameters,
+    pub two_to_one_crh_params: <TwoToOneHash as TwoToOneCRHScheme>::Parameters,
 
-        let claimed_data_com_var_oct = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_oct)?;
-        let claimed_data_com_var_nov = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_nov)?;
-        let claimed_data_com_var_dec = UInt8::new_witness_vec(ns!(cs, "data com"), &self.com_dec)?;
+    /// The data for every period being analyzed. See `AnalysisCircuit::periods`.
+    pub periods: Vec<Period>,
 
-        //
-        // Now we witness our private inputs
-        //
+    /// The root of the public ledger. See `AnalysisCircuit::ledger_root`.
+    pub ledger_root: Option<MerkleRoot>,
+}
 
-        // The amount the data was purchase for
-        let data_purchase_price_jan = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_jan))?;
-        let data_purchase_price_feb = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_feb))?;
-        let data_purchase_price_mar = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_mar))?;
+impl CommittedPeriods {
+    /// Returns the public inputs that `open` allocates, i.e., the ledger root in ledger mode and
+    /// the commitments otherwise
+    pub fn public_inputs(&self) -> Option<Vec<F>> {
+        let ledger_root = self.ledger_root.as_ref();
+        data_public_inputs(ledger_root, &public_coms(ledger_root, &self.periods))
+    }
 
-        let data_purchase_price_apr = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_apr))?;
-        let data_purchase_price_may = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_may))?;
-        let data_purchase_price_jun = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_jun))?;
+    /// Like `public_inputs`, followed by the price bounds that `open_within_bounds` allocates
+    pub fn public_inputs_within_bounds(&self, min: Amount, max: Amount) -> Option<Vec<F>> {
+        Some([self.public_inputs()?, vec![min.0, max.0]].concat())
+    }
 
-        let data_purchase_price_jul = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_jul))?;
-        let data_purchase_price_aug = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_aug))?;
-        let data_purchase_price_sep = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_sep))?;
+    /// Returns the purchase price of every period
+    pub fn purchase_prices(&self) -> Vec<Amount> {
+        self.periods.iter().map(|period| period.data_purchase_price).collect()
+    }
 
-        let data_purchase_price_oct = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_oct))?;
-        let data_purchase_price_nov = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_nov))?;
-        let data_purchase_price_dec = FV::new_witness(ns!(cs, "purchase price"), || Ok(&self.data_purchase_price_dec))?;
+    /// Allocates and opens the data for every period, exactly like AnalysisCircuit does. See
+    /// `open_periods`.
+    pub fn open(&self, cs: ConstraintSystemRef<F>) -> Result<Vec<DataVar>, SynthesisError> {
+        open_periods(
+            cs,
+            &self.leaf_crh_params,
+            &self.two_to_one_crh_params,
+            &self.periods,
+            self.ledger_root.as_ref(),
+        )
+    }
 
+    /// Like `open`, but only returns the purchase price of every period
+    pub fn open_prices(&self, cs: ConstraintSystemRef<F>) -> Result<Vec<FV>, SynthesisError> {
+        let data_vars = self.open(cs)?;
+        Ok(data_vars.into_iter().map(|data_var| data_var.amount.0).collect())
+    }
 
-        let data_com_rand_jan = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_jan))?;
-        let data_com_rand_feb = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_feb))?;
-        let data_com_rand_mar = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_mar))?;
+    /// Like `open`, but also allocates the public price bounds right after the commitments, and
+    /// checks that every purchase price is within them. Returns the data along with the minimum
+    /// and the maximum. See `enforce_price_bounds`.
+    pub fn open_within_bounds(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        min: Amount,
+        max: Amount,
+    ) -> Result<(Vec<DataVar>, (FV, FV)), SynthesisError> {
+        let data_vars = self.open(cs.clone())?;
+        let prices: Vec<FV> = data_vars.iter().map(|data_var| data_var.amount.0.clone()).collect();
+        let bounds = enforce_price_bounds(cs, &prices, min, max)?;
+        Ok((data_vars, bounds))
+    }
 
-        let data_com_rand_apr = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_apr))?;
-        let data_com_rand_may = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_may))?;
-        let data_com_rand_jun = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_jun))?;
+    /// Like `open_within_bounds`, but only returns the purchase price of every period
+    pub fn open_prices_within_bounds(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        min: Amount,
+        max: Amount,
+    ) -> Result<(Vec<FV>, (FV, FV)), SynthesisError> {
+        let (data_vars, bounds) = self.open_within_bounds(cs, min, max)?;
+        let prices = data_vars.into_iter().map(|data_var| data_var.amount.0).collect();
+        Ok((prices, bounds))
+    }
+}
 
-        let data_com_rand_jul = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_jul))?;
-        let data_com_rand_aug = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_aug))?;
-        let data_com_rand_sep = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_sep))?;
+/// Allocates the data of every period and opens its commitment. This is the first thing every
+/// analysis circuit does, and the rest of the circuit works on the returned `DataVar`s.
+///
+/// The public inputs allocated here are the ledger root in ledger mode, and the packed
+/// commitments otherwise. See `data_public_inputs` for the native equivalent.
+pub fn open_periods(
+    cs: ConstraintSystemRef<F>,
+    leaf_crh_params: &<LeafHash as CRHScheme>::Parameters,
+    two_to_one_crh_params: &<TwoToOneHash as TwoToOneCRHScheme>::Parameters,
+    periods: &[Period],
+    ledger_root: Option<&MerkleRoot>,
+) -> Result<Vec<DataVar>, SynthesisError> {
+    let (data_vars, _) = open_periods_with_presence(
+        cs,
+        leaf_crh_params,
+        two_to_one_crh_params,
+        periods,
+        ledger_root,
+        None,
+    )?;
+    Ok(data_vars)
+}
 
-        let data_com_rand_oct = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_oct))?;
-        let data_com_rand_nov = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_nov))?;
-        let data_com_rand_dec = FV::new_witness(ns!(cs, "com rand"), || Ok(&self.data_com_rand_dec))?;
+/// Like `open_periods`, but only opens the commitments of the periods that are present. Returns
+/// the data of every period along with its presence bit. The data of absent periods isn't checked
+/// at all, so the caller must drop it out using the bits.
+///
+/// The bits are public inputs right after the commitments if the mask is public, witnesses if it's
+/// private, and constant `true` if there's no mask. Without a mask this costs exactly as much as
+/// `open_periods`.
+pub fn open_periods_with_presence(
+    cs: ConstraintSystemRef<F>,
+    leaf_crh_params: &<LeafHash as CRHScheme>::Parameters,
+    two_to_one_crh_params: &<TwoToOneHash as TwoToOneCRHScheme>::Parameters,
+    periods: &[Period],
+    ledger_root: Option<&MerkleRoot>,
+    presence: Option<&PresenceMask>,
+) -> Result<(Vec<DataVar>, Vec<Boolean<F>>), SynthesisError> {
+    // First, allocate the public parameters as constants
+    let leaf_crh_params = LeafHashParamsVar::new_constant(cs.clone(), leaf_crh_params)?;
+    let two_to_one_crh_params =
+        TwoToOneHashParamsVar::new_constant(cs.clone(), two_to_one_crh_params)?;
+
+    //
+    // Next, allocate the public inputs. Note the ns! macros are just to create name spaces for
+    // our constraints. It doesn't matter what this does, and it doesn't matter what string you
+    // give it.
+    //
+
+    // Ledger root, if we're in ledger mode
+    let ledger_root_var = ledger_root
+        .map(|root| {
+            <RootVar as AllocVar<MerkleRoot, F>>::new_input(ns!(cs, "ledger root"), || Ok(root))
+        })
+        .transpose()?;
+
+    // Data commitments. These are also the leaves in our tree. Each one is packed into field
+    // elements, the same way `data_public_inputs` packs it for the verifier. In ledger mode they
+    // aren't public at all, so there's nothing to allocate.
+    let claimed_data_com_vars = public_coms(ledger_root, periods)
+        .iter()
+        .map(|com| {
+            let packed_com: Vec<F> = com
+                .to_field_elements()
+                .ok_or(SynthesisError::AssignmentMissing)?;
+            packed_com
+                .iter()
+                .map(|elem| FV::new_input(ns!(cs, "data com"), || Ok(elem)))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Presence bits. These are inputs only if the mask is public.
+    let present_vars = alloc_presence(cs.clone(), presence, periods.len())?;
+
+    //
+    // Now we witness our private inputs
+    //
+
+    // The amount the data was purchase for
+    let data_purchase_prices = periods
+        .iter()
+        .map(|period| {
+            AmountVar::new_witness(ns!(cs, "purchase price"), || Ok(&period.data_purchase_price))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The number of units purchased
+    let data_quantities = periods
+        .iter()
+        .map(|period| FV::new_witness(ns!(cs, "quantity"), || Ok(&period.data_quantity)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let data_com_rands = periods
+        .iter()
+        .map(|period| FV::new_witness(ns!(cs, "com rand"), || Ok(&period.data_com_rand)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Put the pieces of our data together into a DataVar
+    let data_vars: Vec<DataVar> = data_purchase_prices
+        .into_iter()
+        .zip(data_quantities)
+        .map(|(amount, quantity)| DataVar { amount, quantity })
+        .collect();
+
+    // CHECK #1: Data opening.
+    // We "open" each data commitment here. Concretely, we compute the commitment of our data_var
+    // using com_rand_var. We then pack it into field elements and assert that it is equal to the
+    // publicly known commitment. In ledger mode, we instead assert that the commitment is a leaf
+    // in the ledger. Either check is only enforced if the period is present.
+    for (i, (data_var, com_rand)) in data_vars.iter().zip(data_com_rands.iter()).enumerate() {
+        // Generate a commitment to the message
+        let computed_data_com_var = data_var.commit(&leaf_crh_params, com_rand)?;
+
+        match &ledger_root_var {
+            // Verify the commitment
+            None => claimed_data_com_vars[i].conditional_enforce_equal(
+                &computed_data_com_var.to_constraint_field()?,
+                &present_vars[i],
+            )?,
+
+            // Verify the commitment is in the ledger
+            Some(ledger_root_var) => {
+                let path_var = SimplePathVar::new_witness(ns!(cs, "merkle path"), || {
+                    periods[i].path.as_ref().ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                path_var
+                    .verify_membership(
+                        &leaf_crh_params,
+                        &two_to_one_crh_params,
+                        ledger_root_var,
+                        computed_data_com_var.as_slice(),
+                    )?
+                    .conditional_enforce_equal(&Boolean::TRUE, &present_vars[i])?;
+            }
+        }
+    }
 
-        // create input for the result value
+    Ok((data_vars, present_vars))
+}
 
-        let output_purchase_price_avg = FV::new_input(ns!(cs, "purchase price result"), || Ok(&self.output_purchase_price_avg))?;
+/// Allocates the presence bit of each of `num_periods` periods. They are public inputs if the mask
+/// is public, witnesses if it's private, and constant `true` if there's no mask.
+pub fn alloc_presence(
+    cs: ConstraintSystemRef<F>,
+    presence: Option<&PresenceMask>,
+    num_periods: usize,
+) -> Result<Vec<Boolean<F>>, SynthesisError> {
+    match presence {
+        None => Ok(vec![Boolean::TRUE; num_periods]),
+        Some(mask) if mask.present.len() != num_periods => Err(SynthesisError::Unsatisfiable),
+        Some(mask) => {
+            let mode = if mask.is_public { AllocationMode::Input } else { AllocationMode::Witness };
+            mask.present
+                .iter()
+                .map(|present| Boolean::new_variable(ns!(cs, "present"), || Ok(present), mode))
+                .collect()
+        }
+    }
+}
+
+/// Allocates the price bounds and returns the minimum and the maximum. They are public inputs,
+/// unless `bounds_com` is given. Then they are private, and are opened against the public
+/// commitment the same way `open_periods` opens the data commitments.
+pub fn open_bounds(
+    cs: ConstraintSystemRef<F>,
+    leaf_crh_params: &<LeafHash as CRHScheme>::Parameters,
+    bounds: &PriceBounds,
+    bounds_com: Option<&BoundsCommitment>,
+) -> Result<(FV, FV), SynthesisError> {
+    let bounds_com = match bounds_com {
+        Some(bounds_com) => bounds_com,
+        None => {
+            let min = AmountVar::new_input(ns!(cs, "bounds purchase price min"), || {
+                Ok(&bounds.purchase_price_min)
+            })?;
+            let max = AmountVar::new_input(ns!(cs, "bounds purchase price max"), || {
+                Ok(&bounds.purchase_price_max)
+            })?;
+            return Ok((min.0, max.0));
+        }
+    };
+
+    // The commitment is public, and packed like the data commitments
+    let packed_com: Vec<F> = bounds_com
+        .com
+        .to_field_elements()
+        .ok_or(SynthesisError::AssignmentMissing)?;
+    let claimed_bounds_com_var = packed_com
+        .iter()
+        .map(|elem| FV::new_input(ns!(cs, "bounds com"), || Ok(elem)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The bounds and the randomness are private
+    let leaf_crh_params = LeafHashParamsVar::new_constant(cs.clone(), leaf_crh_params)?;
+    let bounds_var = PriceBoundsVar {
+        purchase_price_min: AmountVar::new_witness(ns!(cs, "bounds purchase price min"), || {
+            Ok(&bounds.purchase_price_min)
+        })?,
+        purchase_price_max: AmountVar::new_witness(ns!(cs, "bounds purchase price max"), || {
+            Ok(&bounds.purchase_price_max)
+        })?,
+    };
+    let com_rand = FV::new_witness(ns!(cs, "bounds com rand"), || Ok(&bounds_com.com_rand))?;
+
+    // CHECK: Bounds opening. The committed bounds are the ones the rest of the circuit uses.
+    let computed_bounds_com_var = bounds_var.commit(&leaf_crh_params, &com_rand)?;
+    claimed_bounds_com_var.enforce_equal(&computed_bounds_com_var.to_constraint_field()?)?;
+
+    Ok((bounds_var.purchase_price_min.0, bounds_var.purchase_price_max.0))
+}
+
+/// Allocates the price bounds as public inputs, and checks that every one of `prices` is within
+/// them with `enforce_within_bounds`. Returns the minimum and the maximum.
+pub fn enforce_price_bounds(
+    cs: ConstraintSystemRef<F>,
+    prices: &[FV],
+    min: Amount,
+    max: Amount,
+) -> Result<(FV, FV), SynthesisError> {
+    let bounds_purchase_price_min =
+        AmountVar::new_input(ns!(cs, "bounds purchase price min"), || Ok(min))?.0;
+    let bounds_purchase_price_max =
+        AmountVar::new_input(ns!(cs, "bounds purchase price max"), || Ok(max))?.0;
+    enforce_within_bounds(cs, prices, &bounds_purchase_price_min, &bounds_purchase_price_max)?;
+    Ok((bounds_purchase_price_min, bounds_purchase_price_max))
+}
+
+/// Enforces `min <= value <= max` for every one of `values`. Every value and both bounds are range
+/// checked to `VALUE_BITS` first, so the comparisons only need to decompose small differences.
+/// This also means the maximum is below `max_value_bound()`.
+pub fn enforce_within_bounds(
+    cs: ConstraintSystemRef<F>,
+    values: &[FV],
+    min: &FV,
+    max: &FV,
+) -> Result<(), SynthesisError> {
+    enforce_bit_width(cs.clone(), min, VALUE_BITS)?;
+    enforce_bit_width(cs.clone(), max, VALUE_BITS)?;
+    for value in values {
+        enforce_bit_width(cs.clone(), value, VALUE_BITS)?;
+        enforce_cmp_small(cs.clone(), value, min, Ordering::Greater, true, VALUE_BITS)?;
+        enforce_cmp_small(cs.clone(), value, max, Ordering::Less, true, VALUE_BITS)?;
+    }
+    Ok(())
+}
 
-        // create inputs for the threshold values
+/// Prices and quantities must be below this wherever products of them are computed, which circuits
+/// enforce by bounding the public maximums. With values below 2^64 and fewer than 2^62 periods,
+/// sums of products like `n·Σx²` stay below 2^252, so none of the intermediate values wrap around
+/// the field.
+pub fn max_value_bound() -> F {
+    F::from(1u128 << VALUE_BITS)
+}
 
-        let bounds_purchase_price_min = FV::new_input(ns!(cs, "bounds purchase price"), || Ok(&self.bounds_purchase_price_min))?;
-        let bounds_purchase_price_max = FV::new_input(ns!(cs, "bounds purchase price"), || Ok(&self.bounds_purchase_price_max))?;
+/// Enforces that `claimed` is one of the present values, and that every present value is on the
+/// `ordering` side of it or equal to it. With `Ordering::Greater` this means `claimed` is the
+/// minimum, and with `Ordering::Less` it's the maximum. Absent values are ignored. The present
+/// values must already be range checked to `VALUE_BITS`, and this range checks `claimed`, so the
+/// comparisons are small ones.
+pub fn enforce_extremum(
+    cs: ConstraintSystemRef<F>,
+    values: &[FV],
+    present: &[Boolean<F>],
+    claimed: &FV,
+    ordering: Ordering,
+) -> Result<(), SynthesisError> {
+    // Every present value is on the right side of the claimed one. Absent values are replaced by
+    // the claimed one itself.
+    enforce_bit_width(cs.clone(), claimed, VALUE_BITS)?;
+    for (value, present) in values.iter().zip(present) {
+        let value = present.select(value, claimed)?;
+        enforce_cmp_small(cs.clone(), &value, claimed, ordering, true, VALUE_BITS)?;
+    }
 
+    // The claimed value is one of the present values. This holds iff Π(value - claimed) = 0 over
+    // the present values, since the field has no zero divisors. Absent values contribute a factor
+    // of 1. That's n - 1 constraints, plus a selection per value if there's a mask.
+    let diffs = values
+        .iter()
+        .zip(present)
+        .map(|(value, present)| present.select(&(value - claimed), &FV::one()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let product = diffs
+        .into_iter()
+        .reduce(|acc, diff| acc * diff)
+        .ok_or(SynthesisError::Unsatisfiable)?;
+    product.enforce_equal(&FV::zero())
+}
+
+/// Enforces that `claimed_avg` is `sum / n`, rounded according to `rounding`. We can't divide in
+/// the field, so instead we witness the remainder r and check that `sum + offset = avg * n + r`
+/// with `0 <= r < n`. The caller must bound the average so that `avg * n` can't wrap around the
+/// field. `n` is a `u64`, so the remainder check only needs a `VALUE_BITS` range check.
+pub fn enforce_average(
+    cs: ConstraintSystemRef<F>,
+    sum: &FV,
+    n: u64,
+    claimed_avg: &FV,
+    rounding: RoundingMode,
+) -> Result<(), SynthesisError> {
+    let offset = F::from(rounding.offset(n));
+    let n = F::from(n);
+
+    let remainder = FV::new_witness(ns!(cs, "avg remainder"), || {
+        Ok(sum.value()? + offset - claimed_avg.value()? * n)
+    })?;
+
+    let computed_shifted_sum = claimed_avg * n + &remainder;
+    computed_shifted_sum.enforce_equal(&(sum + offset))?;
+
+    match rounding {
+        RoundingMode::Exact => remainder.enforce_equal(&FV::zero()),
+        RoundingMode::Floor | RoundingMode::Nearest => {
+            enforce_bit_width(cs.clone(), &remainder, VALUE_BITS)?;
+            enforce_cmp_small(cs, &remainder, &FV::constant(n), Ordering::Less, false, VALUE_BITS)
+        }
+    }
+}
+
+/// Like `enforce_average`, but divides by a count that's only known in the circuit, e.g., the
+/// number of present periods. The count must be below `2^(VALUE_BITS - 1)`. The `Nearest` offset
+/// `floor(count / 2)` isn't linear in the count, so that's checked as
+/// `2·sum + count = avg·2·count + r` with `0 <= r < 2·count` instead, which rounds the same way.
+/// There's no average of nothing, so a count of 0 is never satisfied.
+pub fn enforce_average_of_count(
+    cs: ConstraintSystemRef<F>,
+    sum: &FV,
+    count: &FV,
+    claimed_avg: &FV,
+    rounding: RoundingMode,
+) -> Result<(), SynthesisError> {
+    let (dividend, divisor) = match rounding {
+        RoundingMode::Nearest => (sum.double()? + count, count.double()?),
+        RoundingMode::Floor | RoundingMode::Exact => (sum.clone(), count.clone()),
+    };
+
+    let remainder = FV::new_witness(ns!(cs, "avg remainder"), || {
+        Ok(dividend.value()? - claimed_avg.value()? * divisor.value()?)
+    })?;
+
+    let computed_dividend = claimed_avg * &divisor + &remainder;
+    computed_dividend.enforce_equal(&dividend)?;
+
+    // The remainder is below the divisor, which also means the divisor isn't 0
+    enforce_bit_width(cs.clone(), &remainder, VALUE_BITS)?;
+    enforce_cmp_small(cs, &remainder, &divisor, Ordering::Less, false, VALUE_BITS)?;
+    if rounding == RoundingMode::Exact {
+        remainder.enforce_equal(&FV::zero())?;
+    }
+    Ok(())
+}
+
+/// generate_constraints is where the circuit functionality is defined. It doesn't return any
+/// value. Rather, it takes in a constraint system, and adds a bunch of constraints to that system
+/// (implicitly or explicitly). A proof is valid if and only if the final constraint system is
+/// satisfied.
+impl ConstraintSynthesizer<F> for AnalysisCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // Allocate and open the data for every present period. This also allocates the ledger root
+        // or the data commitments as public inputs, followed by the presence mask if it's public.
+        let (data_vars, present) = open_periods_with_presence(
+            cs.clone(),
+            &self.leaf_crh_params,
+            &self.two_to_one_crh_params,
+            &self.periods,
+            self.ledger_root.as_ref(),
+            self.presence.as_ref(),
+        )?;
+        let data_purchase_prices: Vec<FV> =
+            data_vars.iter().map(|data_var| data_var.amount.0.clone()).collect();
+
+        self.enforce_statistics(cs, &data_purchase_prices, &present)
+    }
+}
+
+impl AnalysisCircuit {
+    /// Runs the analysis on the given purchase prices, one per period, where `present` has the
+    /// presence bit of every period. This allocates every public input after the data commitments
+    /// and the presence mask, i.e., the outputs and the bounds. `generate_constraints` runs it on
+    /// the prices it opens from `periods`, and `RollupCircuit` on the totals it sums up from
+    /// transactions.
+    pub fn enforce_statistics(
+        self,
+        cs: ConstraintSystemRef<F>,
+        data_purchase_prices: &[FV],
+        present: &[Boolean<F>],
+    ) -> Result<(), SynthesisError> {
+        // The partition is part of the circuit's shape, so check it's a partition up front. Every
+        // period must be in exactly one group, and every group needs a subtotal.
+        let mut times_grouped = vec![0; data_purchase_prices.len()];
+        for &i in self.partition.iter().flatten() {
+            *times_grouped.get_mut(i).ok_or(SynthesisError::Unsatisfiable)? += 1;
+        }
+        if times_grouped.iter().any(|&times| times != 1)
+            || self.partition.len() != self.output_purchase_price_subtotals.len()
+        {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // create input for the number of present periods, if the mask itself is private
+        let num_present = self
+            .presence
+            .as_ref()
+            .filter(|mask| !mask.is_public)
+            .map(|mask| FV::new_input(ns!(cs, "num present"), || Ok(F::from(mask.num_present()))))
+            .transpose()?;
+
+        // create inputs for the subtotals and the total. If only the interval of the average is
+        // revealed, these are witnesses and the ends of the interval are the inputs.
+        let totals_mode = match self.output_purchase_price_avg_range {
+            None => AllocationMode::Input,
+            Some(_) => AllocationMode::Witness,
+        };
+        let output_purchase_price_subtotals = self
+            .output_purchase_price_subtotals
+            .iter()
+            .map(|subtotal| {
+                let ns = ns!(cs, "purchase price subtotal");
+                AmountVar::new_variable(ns, || Ok(subtotal), totals_mode).map(|subtotal| subtotal.0)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let output_purchase_price_total = AmountVar::new_variable(
+            ns!(cs, "purchase price total"),
+            || Ok(&self.output_purchase_price_total),
+            totals_mode,
+        )?
+        .0;
+        let output_purchase_price_avg_range = match &self.output_purchase_price_avg_range {
+            Some(range) => Some((
+                AmountVar::new_input(ns!(cs, "avg range min"), || Ok(&range.min))?.0,
+                AmountVar::new_input(ns!(cs, "avg range max"), || Ok(&range.max))?.0,
+            )),
+            None => None,
+        };
+        let output_purchase_price_min = self
+            .output_purchase_price_min
+            .as_ref()
+            .map(|min| AmountVar::new_input(ns!(cs, "purchase price min result"), || Ok(min)))
+            .transpose()?
+            .map(|min| min.0);
+        let output_purchase_price_max = self
+            .output_purchase_price_max
+            .as_ref()
+            .map(|max| AmountVar::new_input(ns!(cs, "purchase price max result"), || Ok(max)))
+            .transpose()?
+            .map(|max| max.0);
+
+        // create inputs for the threshold values. If they're hidden, only their commitment is an
+        // input.
+        let bounds = PriceBounds {
+            purchase_price_min: self.bounds_purchase_price_min,
+            purchase_price_max: self.bounds_purchase_price_max,
+        };
+        let (bounds_purchase_price_min, bounds_purchase_price_max) = open_bounds(
+            cs.clone(),
+            &self.leaf_crh_params,
+            &bounds,
+            self.bounds_com.as_ref(),
+        )?;
 
         //
         // Ok everything has been inputted. Now we do the logic of the circuit.
         //
 
-        // Put the pieces of our data together into a CardVar
-        let data_var_jan = DataVar {amount: data_purchase_price_jan.clone()};
-        let data_var_feb = DataVar {amount: data_purchase_price_feb.clone()};
-        let data_var_mar = DataVar {amount: data_purchase_price_mar.clone()};
-
-        let data_var_apr = DataVar {amount: data_purchase_price_apr.clone()};
-        let data_var_may = DataVar {amount: data_purchase_price_may.clone()};
-        let data_var_jun = DataVar {amount: data_purchase_price_jun.clone()};
+        // drop out the absent periods. For the bounds checks their prices are replaced with the
+        // minimum bound, which passes trivially, and for the sums they're replaced with 0. Without
+        // a mask every bit is the constant true, so this costs nothing.
+        let checked_purchase_prices = data_purchase_prices
+            .iter()
+            .zip(present)
+            .map(|(price, present)| present.select(price, &bounds_purchase_price_min))
+            .collect::<Result<Vec<_>, _>>()?;
+        let counted_purchase_prices = data_purchase_prices
+            .iter()
+            .zip(present)
+            .map(|(price, present)| present.select(price, &FV::zero()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // count the present periods, and check the count if it's revealed
+        let computed_num_present = present
+            .iter()
+            .fold(FV::zero(), |acc, bit| acc + FV::from(bit.clone()));
+        if let Some(num_present) = &num_present {
+            computed_num_present.enforce_equal(num_present)?;
+        }
 
-        let data_var_jul = DataVar {amount: data_purchase_price_jul.clone()};
-        let data_var_aug = DataVar {amount: data_purchase_price_aug.clone()};
-        let data_var_sep = DataVar {amount: data_purchase_price_sep.clone()};
+        // bounds constraints. Every price and bound is range checked to VALUE_BITS first, so the
+        // comparisons between them only need to decompose small differences.
+        enforce_within_bounds(
+            cs.clone(),
+            &checked_purchase_prices,
+            &bounds_purchase_price_min,
+            &bounds_purchase_price_max,
+        )?;
+
+        // check subtotals. Each one is a linear combination of the purchase prices we just opened,
+        // so this costs a single constraint per group and can't be chosen freely by the prover.
+        for (group, subtotal) in self.partition.iter().zip(&output_purchase_price_subtotals) {
+            let computed_subtotal: FV = group.iter().map(|&i| &counted_purchase_prices[i]).sum();
+            computed_subtotal.enforce_equal(subtotal)?;
+        }
 
-        let data_var_oct = DataVar {amount: data_purchase_price_oct.clone()};
-        let data_var_nov = DataVar {amount: data_purchase_price_nov.clone()};
-        let data_var_dec = DataVar {amount: data_purchase_price_dec.clone()};
+        // check the total. Every period is in exactly one group, so the subtotals adding up to it
+        // means it's also the sum of all the purchase prices.
+        let computed_total: FV = output_purchase_price_subtotals.iter().sum();
+        computed_total.enforce_equal(&output_purchase_price_total)?;
+
+        // check the average is within the revealed interval, if that's all that's revealed. The
+        // average is over the present periods, and is a witness computed from the total. It's
+        // bounded above, so avg * n can't wrap around the field.
+        if let Some((range_min, range_max)) = &output_purchase_price_avg_range {
+            let output_purchase_price_avg = FV::new_witness(ns!(cs, "purchase price avg"), || {
+                let total = field_to_u128(&output_purchase_price_total.value()?);
+                let count = field_to_u128(&computed_num_present.value()?);
+                let avg = total.zip(count).and_then(|(total, count)| {
+                    let offset = self.rounding.offset(count as u64);
+                    (total + u128::from(offset)).checked_div(count)
+                });
+                Ok(F::from(avg.unwrap_or(0)))
+            })?;
+            enforce_average_of_count(
+                cs.clone(),
+                &output_purchase_price_total,
+                &computed_num_present,
+                &output_purchase_price_avg,
+                self.rounding,
+            )?;
+            enforce_bit_width(cs.clone(), &output_purchase_price_avg, VALUE_BITS)?;
+            enforce_cmp_small(
+                cs.clone(),
+                &output_purchase_price_avg,
+                &bounds_purchase_price_max,
+                Ordering::Less,
+                true,
+                VALUE_BITS,
+            )?;
+
+            enforce_bit_width(cs.clone(), range_min, VALUE_BITS)?;
+            enforce_bit_width(cs.clone(), range_max, VALUE_BITS)?;
+            enforce_cmp_small(
+                cs.clone(),
+                &output_purchase_price_avg,
+                range_min,
+                Ordering::Greater,
+                true,
+                VALUE_BITS,
+            )?;
+            enforce_cmp_small(
+                cs.clone(),
+                &output_purchase_price_avg,
+                range_max,
+                Ordering::Less,
+                true,
+                VALUE_BITS,
+            )?;
+        }
 
-        // CHECK #1: Card opening.
-        // We "open" the data commitment here. Concretely, we compute the commitment of our
-        // data_var using com_rand_var. We then assert that this value is equal to the publicly
-        // known commitment.
+        // check revealed minimum and maximum values, if there are any
+        if let Some(min) = &output_purchase_price_min {
+            enforce_extremum(cs.clone(), data_purchase_prices, present, min, Ordering::Greater)?;
+        }
+        if let Some(max) = &output_purchase_price_max {
+            enforce_extremum(cs.clone(), data_purchase_prices, present, max, Ordering::Less)?;
+        }
 
-        // Generate a commitment to the message
-        let computed_data_comm_var_jan = data_var_jan.commit(&leaf_crh_params, &data_com_rand_jan);
-        let computed_data_comm_var_feb = data_var_feb.commit(&leaf_crh_params, &data_com_rand_feb);
-        let computed_data_comm_var_mar = data_var_mar.commit(&leaf_crh_params, &data_com_rand_mar);
-
-        let computed_data_comm_var_apr = data_var_apr.commit(&leaf_crh_params, &data_com_rand_apr);
-        let computed_data_comm_var_may = data_var_may.commit(&leaf_crh_params, &data_com_rand_may);
-        let computed_data_comm_var_jun = data_var_jun.commit(&leaf_crh_params, &data_com_rand_jun);
-
-        let computed_data_comm_var_jul = data_var_jul.commit(&leaf_crh_params, &data_com_rand_jul);
-        let computed_data_comm_var_aug = data_var_aug.commit(&leaf_crh_params, &data_com_rand_aug);
-        let computed_data_comm_var_sep = data_var_sep.commit(&leaf_crh_params, &data_com_rand_sep);
-
-        let computed_data_comm_var_oct = data_var_oct.commit(&leaf_crh_params, &data_com_rand_oct);
-        let computed_data_comm_var_nov = data_var_nov.commit(&leaf_crh_params, &data_com_rand_nov);
-        let computed_data_comm_var_dec = data_var_dec.commit(&leaf_crh_params, &data_com_rand_dec);
-
-        // Verify the commitment
-        claimed_data_com_var_jan.enforce_equal(&computed_data_comm_var_jan.unwrap())?;
-        claimed_data_com_var_feb.enforce_equal(&computed_data_comm_var_feb.unwrap())?;
-        claimed_data_com_var_mar.enforce_equal(&computed_data_comm_var_mar.unwrap())?;
-
-        claimed_data_com_var_apr.enforce_equal(&computed_data_comm_var_apr.unwrap())?;
-        claimed_data_com_var_may.enforce_equal(&computed_data_comm_var_may.unwrap())?;
-        claimed_data_com_var_jun.enforce_equal(&computed_data_comm_var_jun.unwrap())?;
-
-        claimed_data_com_var_jul.enforce_equal(&computed_data_comm_var_jul.unwrap())?;
-        claimed_data_com_var_aug.enforce_equal(&computed_data_comm_var_aug.unwrap())?;
-        claimed_data_com_var_sep.enforce_equal(&computed_data_comm_var_sep.unwrap())?;
-
-        claimed_data_com_var_oct.enforce_equal(&computed_data_comm_var_oct.unwrap())?;
-        claimed_data_com_var_nov.enforce_equal(&computed_data_comm_var_nov.unwrap())?;
-        claimed_data_com_var_dec.enforce_equal(&computed_data_comm_var_dec.unwrap())?;
-
-        // other code goes here
-
-        // compute sum value
-        let computed_sum_purchase_prices = FV::new_witness(ns!(cs, "intermediate calcs"), || { let tmp = 
-                                                                            self.data_purchase_price_jan + 
-                                                                            self.data_purchase_price_feb + 
-                                                                            self.data_purchase_price_mar + 
-                                                                            self.data_purchase_price_apr + 
-                                                                            self.data_purchase_price_may + 
-                                                                            self.data_purchase_price_jun + 
-                                                                            self.data_purchase_price_jul + 
-                                                                            self.data_purchase_price_aug + 
-                                                                            self.data_purchase_price_sep + 
-                                                                            self.data_purchase_price_oct + 
-                                                                            self.data_purchase_price_nov + 
-                                                                            self.data_purchase_price_dec;
-                                                                            Ok(tmp) })?;
-
-
-        // bounds constraints
-        data_purchase_price_jan.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_jan.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-        data_purchase_price_feb.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_feb.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-        data_purchase_price_mar.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_mar.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-
-        data_purchase_price_apr.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_apr.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-        data_purchase_price_may.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_may.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-        data_purchase_price_jun.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_jun.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-
-        data_purchase_price_jul.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_jul.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-        data_purchase_price_aug.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_aug.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-        data_purchase_price_sep.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_sep.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-
-        data_purchase_price_oct.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_oct.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-        data_purchase_price_nov.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_nov.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-        data_purchase_price_dec.enforce_cmp(&bounds_purchase_price_min, Ordering::Greater, true)?;
-        data_purchase_price_dec.enforce_cmp(&bounds_purchase_price_max, Ordering::Less,    true)?;
-
-        // check sum value
-        computed_sum_purchase_prices.enforce_equal(&output_purchase_price_avg)?;
-
-            // All done with the checks
+        // All done with the checks
         Ok(())
     }
 }
@@ -292,15 +961,21 @@ impl ConstraintSynthesizer<F> for AnalysisCircuit {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::util::{get_test_data, get_test_leaf};
+    use crate::{
+        econ_data::{average, even_partition, subtotals, Data},
+        util::{
+            gen_test_periods, gen_test_tree, get_test_data, get_test_leaf,
+            test_harness::is_satisfied, REVEAL_MIN_MAX,
+        },
+    };
 
     use ark_bls12_381::Fr as F;
     use ark_ff::UniformRand;
     use ark_relations::r1cs::ConstraintSystem;
     use rand::RngCore;
 
-    // Sets up a legitimate possession circuit
-    fn setup(mut rng: impl RngCore) -> AnalysisCircuit {
+    // Sets up a legitimate analysis circuit over `num_periods` periods
+    fn setup(mut rng: impl RngCore, num_periods: usize) -> AnalysisCircuit {
         // Let's set up an RNG for use within tests. Note that this is NOT safe for any production
         // use
 
@@ -321,7 +996,19 @@ mod test {
         let idx_to_prove = our_idx;
         let claimed_leaf = get_test_leaf(&leaf_crh_params, idx_to_prove);
 
-        let claimed_avg = data.purchase_price*F::from(12u32);
+        // Every period has the same price, and they're all in a single group
+        let claimed_total = Amount(data.purchase_price.0 * F::from(num_periods as u64));
+
+        // Every period reuses the same committed data
+        let periods = (0..num_periods)
+            .map(|_| Period {
+                com: claimed_leaf.to_vec(),
+                data_purchase_price: data.purchase_price, // The data's purchase price
+                data_quantity: data.quantity,             // The data's quantity
+                data_com_rand,                            // The data's nonce
+                path: None,
+            })
+            .collect();
 
         // We have everything we need. Build the circuit
         AnalysisCircuit {
@@ -329,61 +1016,84 @@ mod test {
             leaf_crh_params,
             two_to_one_crh_params,
 
-            // Public inputs to the circuit
-            com_jan: claimed_leaf.to_vec(),
-            com_feb: claimed_leaf.to_vec(),
-            com_mar: claimed_leaf.to_vec(),
-
-            com_apr: claimed_leaf.to_vec(),
-            com_may: claimed_leaf.to_vec(),
-            com_jun: claimed_leaf.to_vec(),
-
-            com_jul: claimed_leaf.to_vec(),
-            com_aug: claimed_leaf.to_vec(),
-            com_sep: claimed_leaf.to_vec(),
+            // Public inputs and witnesses for every period
+            periods,
+            ledger_root: None,
+            presence: None,
 
-            com_oct: claimed_leaf.to_vec(),
-            com_nov: claimed_leaf.to_vec(),
-            com_dec: claimed_leaf.to_vec(),
+            partition: vec![(0..num_periods).collect()],
+            rounding: RoundingMode::Floor,
 
-            // Witness to membership
-            // Commitment opening details
-            data_com_rand_jan: data_com_rand,       // The data's nonce
-            data_com_rand_feb: data_com_rand,       // The data's nonce
-            data_com_rand_mar: data_com_rand,       // The data's nonce
+            output_purchase_price_subtotals: vec![claimed_total],   // the output values
+            output_purchase_price_total: claimed_total,
+            output_purchase_price_avg_range: None,
+            output_purchase_price_min: None,
+            output_purchase_price_max: None,
 
-            data_com_rand_apr: data_com_rand,       // The data's nonce
-            data_com_rand_may: data_com_rand,       // The data's nonce
-            data_com_rand_jun: data_com_rand,       // The data's nonce
-
-            data_com_rand_jul: data_com_rand,       // The data's nonce
-            data_com_rand_aug: data_com_rand,       // The data's nonce
-            data_com_rand_sep: data_com_rand,       // The data's nonce
-
-            data_com_rand_oct: data_com_rand,       // The data's nonce
-            data_com_rand_nov: data_com_rand,       // The data's nonce
-            data_com_rand_dec: data_com_rand,       // The data's nonce
+            bounds_purchase_price_min: Amount::from_minor_units(0),          // the minimum bounds
+            bounds_purchase_price_max: Amount::from_minor_units(1000),       // the maximum bounds
+            bounds_com: None,
+        }
+    }
 
-            data_purchase_price_jan: data.purchase_price, // The datas' purchase price
-            data_purchase_price_feb: data.purchase_price, // The datas' purchase price
-            data_purchase_price_mar: data.purchase_price, // The datas' purchase price
+    // Switches a legitimate circuit from `setup` to ledger mode, proving that every period's
+    // commitment is the leaf at `idx` of the test tree
+    fn into_ledger_mode(mut circuit: AnalysisCircuit, idx: usize) -> AnalysisCircuit {
+        let tree = gen_test_tree(&circuit.leaf_crh_params, &circuit.two_to_one_crh_params);
+        for period in circuit.periods.iter_mut() {
+            period.path = Some(tree.generate_proof(idx).unwrap());
+        }
+        circuit.ledger_root = Some(tree.root());
+        circuit
+    }
 
-            data_purchase_price_apr: data.purchase_price, // The datas' purchase price
-            data_purchase_price_may: data.purchase_price, // The datas' purchase price
-            data_purchase_price_jun: data.purchase_price, // The datas' purchase price
+    // Switches a legitimate circuit to hidden bounds mode, committing to its bounds with fresh
+    // randomness
+    fn into_hidden_bounds(mut circuit: AnalysisCircuit, mut rng: impl RngCore) -> AnalysisCircuit {
+        let bounds = PriceBounds {
+            purchase_price_min: circuit.bounds_purchase_price_min,
+            purchase_price_max: circuit.bounds_purchase_price_max,
+        };
+        let com_rand = F::rand(&mut rng);
+        let com = bounds.commit(&circuit.leaf_crh_params, &com_rand).to_vec();
+        circuit.bounds_com = Some(BoundsCommitment { com, com_rand });
+        circuit
+    }
 
-            data_purchase_price_jul: data.purchase_price, // The datas' purchase price
-            data_purchase_price_aug: data.purchase_price, // The datas' purchase price
-            data_purchase_price_sep: data.purchase_price, // The datas' purchase price
+    // Sets up a legitimate analysis circuit where each period commits to the given price. The
+    // periods are in a single group, and the average is rounded with the given rounding mode.
+    fn setup_with_prices(
+        mut rng: impl RngCore,
+        prices: &[u64],
+        rounding: RoundingMode,
+    ) -> AnalysisCircuit {
+        let leaf_crh_params = <LeafHash as CRHScheme>::setup(&mut rng).unwrap();
+        let two_to_one_crh_params = <TwoToOneHash as TwoToOneCRHScheme>::setup(&mut rng).unwrap();
 
-            data_purchase_price_oct: data.purchase_price, // The datas' purchase price
-            data_purchase_price_nov: data.purchase_price, // The datas' purchase price
-            data_purchase_price_dec: data.purchase_price, // The datas' purchase price
+        // Commit to every price with fresh randomness
+        let prices: Vec<Amount> =
+            prices.iter().map(|&price| Amount::from_minor_units(price)).collect();
+        let periods = gen_test_periods(&leaf_crh_params, &prices, &mut rng);
 
-            output_purchase_price_avg: claimed_avg,         // the output value
+        let partition = vec![(0..prices.len()).collect()];
+        let claimed_subtotals = subtotals(&prices, &partition).unwrap();
 
-            bounds_purchase_price_min: F::from(0),          // the minimum bounds
-            bounds_purchase_price_max: F::from(1000),       // the maximum bounds
+        AnalysisCircuit {
+            leaf_crh_params,
+            two_to_one_crh_params,
+            periods,
+            ledger_root: None,
+            presence: None,
+            partition,
+            rounding,
+            output_purchase_price_total: claimed_subtotals[0],
+            output_purchase_price_subtotals: claimed_subtotals,
+            output_purchase_price_avg_range: None,
+            output_purchase_price_min: None,
+            output_purchase_price_max: None,
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+            bounds_com: None,
         }
     }
 
@@ -391,9 +1101,7 @@ mod test {
     #[test]
     fn data_correctness() {
         let mut rng = ark_std::test_rng();
-        let circuit = setup(&mut rng);
-
-        println!("data_purchase_price_jan = {}",circuit.data_purchase_price_jan);
+        let circuit = setup(&mut rng, 12);
 
         // Run the circuit on a fresh constraint system
         let cs = ConstraintSystem::new_ref();
@@ -412,8 +1120,8 @@ mod test {
     fn data_soundness() {
         // Make a new circuit and maul its purchase price
         let mut rng = ark_std::test_rng();
-        let mut bad_data_circuit = setup(&mut rng);
-        bad_data_circuit.data_purchase_price_jan = F::rand(&mut rng);
+        let mut bad_data_circuit = setup(&mut rng, 12);
+        bad_data_circuit.periods[0].data_purchase_price = Amount(F::rand(&mut rng));
 
         // Run the circuit on a fresh constraint system
         let cs = ConstraintSystem::new_ref();
@@ -426,4 +1134,427 @@ mod test {
             "circuit should not be satisfied after changing the data purchase price"
         );
     }
+
+    // The circuit shape isn't tied to 12 months. Make sure it also works over 4 quarters.
+    #[test]
+    fn quarterly_correctness() {
+        let mut rng = ark_std::test_rng();
+        let circuit = setup(&mut rng, 4);
+
+        // Run the circuit on a fresh constraint system
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+
+        assert!(
+            cs.is_satisfied().unwrap(),
+            "circuit correctness check failed for a 4 period circuit"
+        );
+    }
+
+    // Sum soundness test: Claim a different total than the committed prices add up to. Every
+    // commitment still opens correctly, but the proof should fail since the claimed output isn't
+    // tied to anything but the opened prices.
+    #[test]
+    fn sum_soundness() {
+        let mut rng = ark_std::test_rng();
+        let mut bad_sum_circuit = setup(&mut rng, 12);
+        bad_sum_circuit.output_purchase_price_total.0 += F::from(1u32);
+
+        // Run the circuit on a fresh constraint system
+        let cs = ConstraintSystem::new_ref();
+        bad_sum_circuit.generate_constraints(cs.clone()).unwrap();
+
+        assert!(
+            !cs.is_satisfied().unwrap(),
+            "circuit should not be satisfied after changing the claimed total"
+        );
+    }
+
+    // Rounding test: 100, 200, 201 average to 167 exactly, 200, 200, 201 average to 200.33, and
+    // 200, 201, 201 average to 200.67. Check each rounding mode gives the expected value and that
+    // the circuit accepts an interval of just that value but not of its neighbors.
+    #[test]
+    fn average_rounding() {
+        let mut rng = ark_std::test_rng();
+        let just = |avg| {
+            let avg = Amount::from_minor_units(avg);
+            Some(AmountInterval { min: avg, max: avg })
+        };
+
+        for (prices, rounding, expected_avg) in [
+            (&[100, 200, 201], RoundingMode::Exact, 167u64),
+            (&[200, 200, 201], RoundingMode::Floor, 200),
+            (&[200, 200, 201], RoundingMode::Nearest, 200),
+            (&[200, 201, 201], RoundingMode::Floor, 200),
+            (&[200, 201, 201], RoundingMode::Nearest, 201),
+        ] {
+            let amounts = prices.map(Amount::from_minor_units);
+            assert_eq!(average(&amounts, rounding), Some(Amount::from_minor_units(expected_avg)));
+
+            let mut circuit = setup_with_prices(&mut rng, prices, rounding);
+            circuit.output_purchase_price_avg_range = just(expected_avg);
+            assert!(is_satisfied(circuit.clone()), "{rounding:?} average of {prices:?} failed");
+
+            circuit.output_purchase_price_avg_range = just(expected_avg + 1);
+            assert!(!is_satisfied(circuit.clone()), "{rounding:?} accepted an average too high");
+
+            circuit.output_purchase_price_avg_range = just(expected_avg - 1);
+            assert!(!is_satisfied(circuit), "{rounding:?} accepted an average that's too low");
+        }
+
+        // An exact average doesn't exist here, so neither the floor nor the ceiling is accepted
+        let prices = [200, 200, 201].map(Amount::from_minor_units);
+        assert_eq!(average(&prices, RoundingMode::Exact), None);
+        let mut inexact = setup_with_prices(&mut rng, &[200, 200, 201], RoundingMode::Exact);
+        for claimed_avg in [200, 201] {
+            inexact.output_purchase_price_avg_range = just(claimed_avg);
+            assert!(!is_satisfied(inexact.clone()), "inexact average {claimed_avg} accepted");
+        }
+    }
+
+    // Statement test: The verifier rebuilds the public inputs from the statement alone. Make sure
+    // they are exactly what the circuit allocated, and that the commitments are packed.
+    #[test]
+    fn statement_matches_public_inputs() {
+        let mut rng = ark_std::test_rng();
+        let circuit = setup(&mut rng, 12);
+        let public_inputs = circuit.statement().to_field_elements().unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // The first instance variable is always the constant 1
+        let instance = cs.borrow().unwrap().instance_assignment.clone();
+        assert_eq!(instance[1..], public_inputs[..]);
+
+        // 12 commitments of 3 field elements each, plus the single subtotal, the total and the
+        // two bounds
+        assert_eq!(public_inputs.len(), 12 * 3 + 4);
+    }
+
+    // Constraint count test: The 12-month circuit compares its prices, bounds and, as shipped,
+    // its revealed minimum and maximum with small range checks. Count what the same comparisons
+    // would cost with `FpVar::enforce_cmp`, and make sure everything past opening the commitments
+    // costs less than a tenth of that.
+    #[test]
+    fn range_check_savings() {
+        let mut rng = ark_std::test_rng();
+        let mut circuit = setup(&mut rng, 12);
+        let prices: Vec<Amount> = circuit.periods.iter().map(|p| p.data_purchase_price).collect();
+        let claimed_min = prices.iter().min_by_key(|price| field_to_u128(&price.0)).copied();
+        let claimed_max = prices.iter().max_by_key(|price| field_to_u128(&price.0)).copied();
+        circuit.output_purchase_price_min = claimed_min.filter(|_| REVEAL_MIN_MAX);
+        circuit.output_purchase_price_max = claimed_max.filter(|_| REVEAL_MIN_MAX);
+
+        // Everything the circuit does past opening the commitments
+        let opening_cs = ConstraintSystem::new_ref();
+        open_periods(
+            opening_cs.clone(),
+            &circuit.leaf_crh_params,
+            &circuit.two_to_one_crh_params,
+            &circuit.periods,
+            None,
+        )
+        .unwrap();
+        let cs = ConstraintSystem::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        let analysis_cost = cs.num_constraints() - opening_cs.num_constraints();
+
+        // The same comparisons with full field decompositions
+        let full_cs = ConstraintSystem::new_ref();
+        let witness = |value: F| FV::new_witness(ns!(full_cs, "value"), || Ok(value)).unwrap();
+        let min = witness(circuit.bounds_purchase_price_min.0);
+        let max = witness(circuit.bounds_purchase_price_max.0);
+        let claimed_min = circuit.output_purchase_price_min.map(|min| witness(min.0));
+        let claimed_max = circuit.output_purchase_price_max.map(|max| witness(max.0));
+        for period in &circuit.periods {
+            let price = witness(period.data_purchase_price.0);
+            price.enforce_cmp(&min, Ordering::Greater, true).unwrap();
+            price.enforce_cmp(&max, Ordering::Less, true).unwrap();
+            if let Some(claimed_min) = &claimed_min {
+                price.enforce_cmp(claimed_min, Ordering::Greater, true).unwrap();
+            }
+            if let Some(claimed_max) = &claimed_max {
+                price.enforce_cmp(claimed_max, Ordering::Less, true).unwrap();
+            }
+        }
+        let full_cost = full_cs.num_constraints();
+
+        assert!(10 * analysis_cost < full_cost, "range checks cost {analysis_cost} of {full_cost}");
+    }
+
+    // Ledger correctness test: Every commitment is the 8th leaf of the test tree. Only the root is
+    // public, so the verifier doesn't see the commitments at all.
+    #[test]
+    fn ledger_correctness() {
+        let mut rng = ark_std::test_rng();
+        let circuit = into_ledger_mode(setup(&mut rng, 4), 7);
+        let public_inputs = circuit.statement().to_field_elements().unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(
+            cs.is_satisfied().unwrap(),
+            "circuit correctness check failed in ledger mode"
+        );
+
+        // The root is 2 field elements, followed by the subtotal, the total and the two bounds
+        let instance = cs.borrow().unwrap().instance_assignment.clone();
+        assert_eq!(instance[1..], public_inputs[..]);
+        assert_eq!(public_inputs.len(), 2 + 4);
+    }
+
+    // Ledger soundness test: Use the authentication path of a different leaf. The commitments
+    // still open correctly, but they are no longer proven to be in the ledger.
+    #[test]
+    fn ledger_soundness() {
+        let mut rng = ark_std::test_rng();
+        let bad_path_circuit = into_ledger_mode(setup(&mut rng, 4), 3);
+
+        assert!(
+            !is_satisfied(bad_path_circuit),
+            "circuit should not be satisfied with a path to the wrong leaf"
+        );
+    }
+
+    // Hidden bounds test: The bounds are opened against their commitment, and the verifier only
+    // sees the commitment. Bounds other than the committed ones are rejected, even if the prices
+    // are within them.
+    #[test]
+    fn hidden_bounds() {
+        let mut rng = ark_std::test_rng();
+        let circuit = into_hidden_bounds(setup(&mut rng, 12), &mut rng);
+        let statement = circuit.statement();
+        assert_eq!(statement.outputs.bounds_purchase_price_min, None);
+        assert_eq!(statement.outputs.bounds_purchase_price_max, None);
+
+        let public_inputs = statement.to_field_elements().unwrap();
+        let cs = ConstraintSystem::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap(), "hidden bounds correctness check failed");
+
+        // 12 commitments, the subtotal and total, and the bounds commitment of 3 field elements
+        let instance = cs.borrow().unwrap().instance_assignment.clone();
+        assert_eq!(instance[1..], public_inputs[..]);
+        assert_eq!(public_inputs.len(), 12 * 3 + 2 + 3);
+
+        let mut bad_bounds = circuit.clone();
+        bad_bounds.bounds_purchase_price_max = Amount::from_minor_units(2000);
+        assert!(!is_satisfied(bad_bounds), "accepted bounds that weren't committed to");
+
+        let mut bad_rand = circuit.clone();
+        bad_rand.bounds_com.as_mut().unwrap().com_rand += F::from(1u32);
+        assert!(!is_satisfied(bad_rand), "accepted the wrong commitment randomness");
+
+        // A data commitment to the same bytes is a different commitment, and doesn't open as
+        // bounds
+        let bounds_com = circuit.bounds_com.clone().unwrap();
+        let bounds = PriceBounds {
+            purchase_price_min: circuit.bounds_purchase_price_min,
+            purchase_price_max: circuit.bounds_purchase_price_max,
+        };
+        let data = Data {
+            purchase_price: bounds.purchase_price_min,
+            quantity: bounds.purchase_price_max.0,
+        };
+        let data_com = data.commit(&circuit.leaf_crh_params, &bounds_com.com_rand).to_vec();
+        let bounds_com_again = bounds.commit(&circuit.leaf_crh_params, &bounds_com.com_rand);
+        assert_eq!(bounds_com_again.to_vec(), bounds_com.com);
+        assert_ne!(data_com, bounds_com.com);
+
+        let mut data_as_bounds = circuit;
+        data_as_bounds.bounds_com.as_mut().unwrap().com = data_com;
+        assert!(!is_satisfied(data_as_bounds), "accepted a data commitment as bounds");
+    }
+
+    // Range-only test: Every period costs 500, so the hidden average is 500. The verifier only
+    // sees the interval, not the totals, and intervals that don't contain the average are
+    // rejected.
+    #[test]
+    fn avg_range_only() {
+        let mut rng = ark_std::test_rng();
+        let mut circuit = setup(&mut rng, 12);
+        let interval = |min, max| AmountInterval {
+            min: Amount::from_minor_units(min),
+            max: Amount::from_minor_units(max),
+        };
+
+        circuit.output_purchase_price_avg_range = Some(interval(400, 600));
+        let statement = circuit.statement();
+        assert!(statement.outputs.output_purchase_price_subtotals.is_empty());
+        assert_eq!(statement.outputs.output_purchase_price_total, None);
+
+        let public_inputs = statement.to_field_elements().unwrap();
+        let cs = ConstraintSystem::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap(), "range-only correctness check failed");
+
+        // 12 commitments, the ends of the interval and the two bounds
+        let instance = cs.borrow().unwrap().instance_assignment.clone();
+        assert_eq!(instance[1..], public_inputs[..]);
+        assert_eq!(public_inputs.len(), 12 * 3 + 2 + 2);
+
+        for (min, max) in [(500, 500), (0, 1000)] {
+            circuit.output_purchase_price_avg_range = Some(interval(min, max));
+            assert!(is_satisfied(circuit.clone()), "rejected an average in [{min}, {max}]");
+        }
+        for (min, max) in [(501, 600), (400, 499), (600, 400)] {
+            circuit.output_purchase_price_avg_range = Some(interval(min, max));
+            assert!(!is_satisfied(circuit.clone()), "accepted an average in [{min}, {max}]");
+        }
+
+        // The hidden total still has to be the true one
+        circuit.output_purchase_price_avg_range = Some(interval(400, 600));
+        circuit.output_purchase_price_total.0 += F::from(1u32);
+        assert!(!is_satisfied(circuit), "accepted a wrong hidden total");
+    }
+
+    // Min/max test: Reveal the minimum and maximum in the same proof as the average. Then try
+    // revealing values that are in range but not committed, and committed values that aren't the
+    // extremes.
+    #[test]
+    fn min_max_reveal() {
+        let mut rng = ark_std::test_rng();
+        let mut circuit = setup_with_prices(&mut rng, &[300, 100, 700, 500], RoundingMode::Floor);
+        circuit.output_purchase_price_min = Some(Amount::from_minor_units(100));
+        circuit.output_purchase_price_max = Some(Amount::from_minor_units(700));
+        assert!(is_satisfied(circuit.clone()), "min/max reveal correctness check failed");
+
+        // The subtotal, total, minimum and maximum are all public
+        assert_eq!(circuit.statement().to_field_elements().unwrap().len(), 4 * 3 + 6);
+
+        for bad_min in [0, 99, 300] {
+            let mut bad_circuit = circuit.clone();
+            bad_circuit.output_purchase_price_min = Some(Amount::from_minor_units(bad_min));
+            assert!(!is_satisfied(bad_circuit), "accepted {bad_min} as the minimum");
+        }
+        for bad_max in [1000, 701, 500] {
+            let mut bad_circuit = circuit.clone();
+            bad_circuit.output_purchase_price_max = Some(Amount::from_minor_units(bad_max));
+            assert!(!is_satisfied(bad_circuit), "accepted {bad_max} as the maximum");
+        }
+    }
+
+    // Partition test: Report subtotals for quarters, fiscal halves, and odd and even months in
+    // the same proof as the annual total. Subtotals that still add up to the total but don't
+    // match their groups are rejected, and so are partitions that miss or repeat a period.
+    #[test]
+    fn partition_subtotals() {
+        let mut rng = ark_std::test_rng();
+        let prices: Vec<u64> = (1..=12).map(|month| 10 * month).collect();
+        let circuit = setup_with_prices(&mut rng, &prices, RoundingMode::Floor);
+        let amounts: Vec<Amount> = prices.iter().map(|&p| Amount::from_minor_units(p)).collect();
+        let with_partition = |partition: Vec<Vec<usize>>| {
+            let mut circuit = circuit.clone();
+            circuit.output_purchase_price_subtotals = subtotals(&amounts, &partition).unwrap();
+            circuit.partition = partition;
+            circuit
+        };
+
+        let quarters = even_partition(12, 4).unwrap();
+        let halves = even_partition(12, 2).unwrap();
+        let odd_even = vec![(0..12).step_by(2).collect(), (1..12).step_by(2).collect()];
+        for partition in [quarters.clone(), halves, odd_even] {
+            let num_groups = partition.len();
+            let circuit = with_partition(partition);
+            let public_inputs = circuit.statement().to_field_elements().unwrap();
+
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert!(cs.is_satisfied().unwrap(), "rejected {num_groups} honest subtotals");
+
+            // 12 commitments, a subtotal per group, the total and the two bounds
+            let instance = cs.borrow().unwrap().instance_assignment.clone();
+            assert_eq!(instance[1..], public_inputs[..]);
+            assert_eq!(public_inputs.len(), 12 * 3 + num_groups + 1 + 2);
+        }
+
+        // The Q1 subtotal is 60 and Q2 is 150. Moving 1 between them keeps the total.
+        let mut shifted = with_partition(quarters.clone());
+        assert_eq!(shifted.output_purchase_price_subtotals[0], Amount::from_minor_units(60));
+        shifted.output_purchase_price_subtotals[0].0 += F::from(1u32);
+        shifted.output_purchase_price_subtotals[1].0 -= F::from(1u32);
+        assert!(!is_satisfied(shifted), "accepted subtotals that don't match their quarters");
+
+        // Partitions are part of the circuit's shape, so bad ones are rejected up front
+        let mut missing = quarters.clone();
+        missing[3].pop();
+        let mut repeated = quarters;
+        repeated[3].push(0);
+        for partition in [missing, repeated, vec![(0..13).collect()]] {
+            let mut bad_partition = circuit.clone();
+            let num_groups = partition.len();
+            bad_partition.output_purchase_price_subtotals = vec![Amount::default(); num_groups];
+            bad_partition.partition = partition;
+            let cs = ConstraintSystem::new_ref();
+            assert!(bad_partition.generate_constraints(cs).is_err(), "accepted a bad partition");
+        }
+    }
+
+    // Presence test: The store was closed in August, so there's only a placeholder with no
+    // commitment for that month. The totals, the extremes and the average all skip it, whether the
+    // mask is public or only the number of present months is. Claiming August is present fails
+    // since it doesn't open, and so does leaving out a month that's in the total.
+    #[test]
+    fn presence_mask() {
+        let mut rng = ark_std::test_rng();
+        let prices: Vec<u64> = (0..12).map(|month| 300 + 10 * month).collect();
+        let mut circuit = setup_with_prices(&mut rng, &prices, RoundingMode::Floor);
+
+        let august = 7;
+        circuit.periods[august].com = vec![0u8; circuit.periods[august].com.len()];
+        circuit.periods[august].data_purchase_price = Amount(F::rand(&mut rng));
+        let mut present = vec![true; 12];
+        present[august] = false;
+
+        // The 11 present months add up to 3890, so their average is 353.6
+        let present_prices: Vec<Amount> = (0..12)
+            .filter(|&month| present[month])
+            .map(|month| Amount::from_minor_units(prices[month]))
+            .collect();
+        let total = Amount(present_prices.iter().map(|price| price.0).sum());
+        let avg = average(&present_prices, RoundingMode::Floor).unwrap();
+        assert_eq!(total, Amount::from_minor_units(3890));
+        assert_eq!(avg, Amount::from_minor_units(353));
+        circuit.output_purchase_price_subtotals = vec![total];
+        circuit.output_purchase_price_total = total;
+        circuit.output_purchase_price_min = Some(Amount::from_minor_units(300));
+        circuit.output_purchase_price_max = Some(Amount::from_minor_units(410));
+
+        for is_public in [true, false] {
+            let mut circuit = circuit.clone();
+            circuit.presence = Some(PresenceMask { present: present.clone(), is_public });
+            let public_inputs = circuit.statement().to_field_elements().unwrap();
+
+            let cs = ConstraintSystem::new_ref();
+            circuit.clone().generate_constraints(cs.clone()).unwrap();
+            assert!(cs.is_satisfied().unwrap(), "rejected a missing month, public: {is_public}");
+
+            // 12 commitments, the mask or the count, the subtotal, the total, the minimum, the
+            // maximum and the two bounds
+            let mask_len = if is_public { 12 } else { 1 };
+            let instance = cs.borrow().unwrap().instance_assignment.clone();
+            assert_eq!(instance[1..], public_inputs[..]);
+            assert_eq!(public_inputs.len(), 12 * 3 + mask_len + 6);
+
+            // The hidden average divides by 11, not 12
+            let just = |avg| Some(AmountInterval { min: avg, max: avg });
+            circuit.output_purchase_price_avg_range = just(avg);
+            assert!(is_satisfied(circuit.clone()), "rejected the average of the 11 months");
+            circuit.output_purchase_price_avg_range = just(Amount::from_minor_units(3890 / 12));
+            assert!(!is_satisfied(circuit), "accepted the average over all 12 months");
+        }
+
+        let mut all_present = circuit.clone();
+        all_present.presence = Some(PresenceMask { present: vec![true; 12], is_public: false });
+        assert!(!is_satisfied(all_present), "accepted a placeholder as a present month");
+
+        let mut march_missing = present;
+        march_missing[2] = false;
+        let mut hidden_march = circuit;
+        hidden_march.presence = Some(PresenceMask { present: march_missing, is_public: false });
+        assert!(!is_satisfied(hidden_march), "accepted a total that includes a missing month");
+    }
 }
@@ -0,0 +1,304 @@
+use crate::{
+    amount::Amount,
+    constraints::{CommittedPeriods, PublicInputs},
+    range::{enforce_bit_width, enforce_cmp_small},
+    F, FV,
+};
+
+use ark_r1cs_std::{alloc::AllocVar, fields::FieldVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// The most periods a correlation circuit can have. See `max_correlation_value_bound`.
+pub const MAX_CORRELATION_PERIODS: usize = 1 << 10;
+
+/// The maximum price and maximum quantity must be below this. The circuit squares the covariance,
+/// so the bound is much tighter than `max_value_bound()`. With values below 2^32 and at most 2^10
+/// periods, the covariance and both variances are below 2^84, and the products compared at the
+/// end are below 2^84·2^84·2^32 = 2^200, far from the size of the BLS12-381 scalar field.
+pub fn max_correlation_value_bound() -> F {
+    F::from(1u64 << CORRELATION_VALUE_BITS)
+}
+
+/// The numerator and denominator of the correlation bound must be below this. See
+/// `max_correlation_value_bound`.
+pub fn max_correlation_ratio_bound() -> F {
+    F::from(1u64 << CORRELATION_RATIO_BITS)
+}
+
+// The bit widths of the bounds above, and of the values the circuit compares. The scaled
+// covariance is below 2^84, and both sides of the final comparison are below 2^200.
+const CORRELATION_VALUE_BITS: usize = 32;
+const CORRELATION_RATIO_BITS: usize = 16;
+const COVARIANCE_BITS: usize = 84;
+const PRODUCT_BITS: usize = 200;
+
+/// A ZK circuit that proves "the purchase prices and quantities were correlated above ρ", e.g.,
+/// price and volume correlated above 0.7 this year, without revealing either series. Both series
+/// come from the same committed `Data`, so each period opens one commitment.
+///
+/// The Pearson correlation is `r = C / sqrt(V_x · V_y)`, where `C = n·Σxy − Σx·Σy` is `n²` times
+/// the covariance, and `V_x = n·Σx² − (Σx)²`, `V_y = n·Σy² − (Σy)²` are `n²` times the variances.
+/// For a bound `ρ = a / b` with `a >= 0`, `r >= ρ` iff `C > 0` and `b²·C² >= a²·V_x·V_y`. So we
+/// square both sides instead of taking a square root.
+#[derive(Clone)]
+pub struct CorrelationCircuit {
+    /// The committed data for every period, and the ledger it's in, if any. There can be at most
+    /// `MAX_CORRELATION_PERIODS` periods.
+    pub data: CommittedPeriods,
+
+    // The claimed lower bound on the correlation, as the fraction `num / den`. Use
+    // `econ_data::correlation` to pick one. Both must be below `max_correlation_ratio_bound()`.
+    pub bounds_correlation_num: F,
+    pub bounds_correlation_den: F,
+
+    // The analysis bounds. The maximums must be below `max_correlation_value_bound()`.
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+    pub bounds_quantity_max: F,
+}
+
+impl PublicInputs for CorrelationCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                vec![
+                    self.bounds_correlation_num,
+                    self.bounds_correlation_den,
+                    self.bounds_quantity_max,
+                ],
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for CorrelationCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // The bounds below only hold for a limited number of periods
+        if self.data.periods.len() > MAX_CORRELATION_PERIODS {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // allocate and open the committed data, and check every price is within the bounds
+        let (data_vars, (_, bounds_purchase_price_max)) = self.data.open_within_bounds(
+            cs.clone(),
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        // create inputs for the claimed correlation bound
+        let bounds_correlation_num = FV::new_input(ns!(cs, "bounds correlation num"), || {
+            Ok(&self.bounds_correlation_num)
+        })?;
+        let bounds_correlation_den = FV::new_input(ns!(cs, "bounds correlation den"), || {
+            Ok(&self.bounds_correlation_den)
+        })?;
+
+        // create input for the quantity bound
+        let bounds_quantity_max =
+            FV::new_input(ns!(cs, "bounds quantity"), || Ok(&self.bounds_quantity_max))?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // bounds constraints. These are what keep every product below from wrapping around the
+        // field. See `max_correlation_value_bound`. The prices are at most their maximum, so
+        // checking the maximum's width covers them too.
+        enforce_bit_width(cs.clone(), &bounds_purchase_price_max, CORRELATION_VALUE_BITS)?;
+        enforce_bit_width(cs.clone(), &bounds_quantity_max, CORRELATION_VALUE_BITS)?;
+        enforce_bit_width(cs.clone(), &bounds_correlation_num, CORRELATION_RATIO_BITS)?;
+        enforce_bit_width(cs.clone(), &bounds_correlation_den, CORRELATION_RATIO_BITS)?;
+        enforce_cmp_small(
+            cs.clone(),
+            &bounds_correlation_den,
+            &FV::zero(),
+            Ordering::Greater,
+            false,
+            CORRELATION_RATIO_BITS,
+        )?;
+        for data_var in &data_vars {
+            enforce_bit_width(cs.clone(), &data_var.quantity, CORRELATION_VALUE_BITS)?;
+            enforce_cmp_small(
+                cs.clone(),
+                &data_var.quantity,
+                &bounds_quantity_max,
+                Ordering::Less,
+                true,
+                CORRELATION_VALUE_BITS,
+            )?;
+        }
+
+        // compute Σx, Σy, Σxy, Σx² and Σy², where x is the price and y the quantity. The sums
+        // are free, and each product is one constraint.
+        let n = F::from(self.data.periods.len() as u64);
+        let prices: Vec<&FV> = data_vars.iter().map(|data_var| &data_var.amount.0).collect();
+        let quantities: Vec<&FV> = data_vars.iter().map(|data_var| &data_var.quantity).collect();
+        let sum_x: FV = prices.iter().copied().sum();
+        let sum_y: FV = quantities.iter().copied().sum();
+        let sum_xy = prices
+            .iter()
+            .zip(quantities.iter())
+            .map(|(&x, &y)| x * y)
+            .collect::<Vec<_>>()
+            .iter()
+            .sum::<FV>();
+        let sum_xx = prices
+            .iter()
+            .map(|x| x.square())
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .sum::<FV>();
+        let sum_yy = quantities
+            .iter()
+            .map(|y| y.square())
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .sum::<FV>();
+
+        // compute the scaled covariance and variances
+        let covariance = sum_xy * n - &sum_x * &sum_y;
+        let variance_x = sum_xx * n - sum_x.square()?;
+        let variance_y = sum_yy * n - sum_y.square()?;
+
+        // CHECK: the correlation is positive. A negative covariance is close to p, so the range
+        // check rules it out.
+        enforce_bit_width(cs.clone(), &covariance, COVARIANCE_BITS)?;
+        enforce_cmp_small(
+            cs.clone(),
+            &covariance,
+            &FV::zero(),
+            Ordering::Greater,
+            false,
+            COVARIANCE_BITS,
+        )?;
+
+        // CHECK: b²·C² >= a²·V_x·V_y, i.e., r² >= (a/b)². Since r > 0 and a >= 0, this means
+        // r >= a/b. Both sides are non-negative integers below 2^200.
+        let lhs = bounds_correlation_den.square()? * covariance.square()?;
+        let rhs = bounds_correlation_num.square()? * (variance_x * variance_y);
+        enforce_bit_width(cs.clone(), &lhs, PRODUCT_BITS)?;
+        enforce_bit_width(cs.clone(), &rhs, PRODUCT_BITS)?;
+        enforce_cmp_small(cs, &lhs, &rhs, Ordering::Greater, true, PRODUCT_BITS)?;
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        econ_data::{correlation, Data},
+        util::test_harness::{gen_test_committed_data, is_satisfied},
+    };
+
+    // Sets up a correlation circuit over the given (price, quantity) pairs, claiming a correlation
+    // of at least num / den
+    fn setup(data: &[(u64, u64)], num: u64, den: u64) -> CorrelationCircuit {
+        CorrelationCircuit {
+            data: gen_test_committed_data(data),
+            bounds_correlation_num: F::from(num),
+            bounds_correlation_den: F::from(den),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+            bounds_quantity_max: F::from(1000),
+        }
+    }
+
+    fn to_data(pairs: &[(u64, u64)]) -> Vec<Data> {
+        pairs
+            .iter()
+            .map(|&(p, q)| Data {
+                purchase_price: Amount::from_minor_units(p),
+                quantity: F::from(q),
+            })
+            .collect()
+    }
+
+    // Tightness test: For prices 1, 2, 3 and quantities 1, 3, 2 we have C = 3·13 − 6·6 = 3 and
+    // V_x = V_y = 3·14 − 36 = 6, so r is exactly 1/2. Claiming exactly 1/2 makes both sides of
+    // b²·C² >= a²·V_x·V_y equal, and anything higher is rejected.
+    #[test]
+    fn correlation_exact_bound() {
+        let pairs = [(1, 1), (2, 3), (3, 2)];
+        assert_eq!(correlation(&to_data(&pairs)), Some(0.5));
+
+        for (num, den) in [(1, 2), (500, 1000), (0, 1)] {
+            assert!(is_satisfied(setup(&pairs, num, den)), "rejected a bound of {num}/{den}");
+        }
+        for (num, den) in [(501, 1000), (1, 1)] {
+            assert!(!is_satisfied(setup(&pairs, num, den)), "accepted a bound of {num}/{den}");
+        }
+    }
+
+    // Perfect correlation test: A linear series has r = 1, so the bound 1/1 is met with equality.
+    // Nudging one quantity off the line drops r just below 1.
+    #[test]
+    fn correlation_perfect() {
+        let linear = [(100, 10), (200, 20), (300, 30), (400, 40)];
+        assert_eq!(correlation(&to_data(&linear)), Some(1.0));
+        assert!(is_satisfied(setup(&linear, 1, 1)), "rejected a correlation of exactly 1");
+
+        let nudged = [(100, 10), (200, 20), (300, 30), (400, 41)];
+        let r = correlation(&to_data(&nudged)).unwrap();
+        assert!(r > 0.999 && r < 1.0);
+        assert!(is_satisfied(setup(&nudged, 999, 1000)), "rejected a correlation of 0.999");
+        assert!(!is_satisfied(setup(&nudged, 1, 1)), "accepted a correlation of 1");
+    }
+
+    // Sign test: Squaring loses the sign of C, so a perfectly negative correlation would pass the
+    // final check if the covariance weren't checked to be positive. A constant series has C = 0,
+    // which isn't a positive correlation either.
+    #[test]
+    fn correlation_sign() {
+        let anti_correlated = [(100, 40), (200, 30), (300, 20), (400, 10)];
+        assert_eq!(correlation(&to_data(&anti_correlated)), Some(-1.0));
+        let circuit = setup(&anti_correlated, 1, 1);
+        assert!(!is_satisfied(circuit), "accepted a negative correlation");
+
+        let constant = [(100, 10), (200, 10), (300, 10), (400, 10)];
+        let circuit = setup(&constant, 0, 1);
+        assert!(!is_satisfied(circuit), "accepted a correlation with a constant series");
+    }
+
+    // Bounds test: The ratio needs a nonzero denominator, and every bound has to be small enough
+    // that the squared products can't wrap around the field. Quantities above their maximum are
+    // rejected like prices.
+    #[test]
+    fn correlation_bounds() {
+        let pairs = [(1, 1), (2, 3), (3, 2)];
+        assert!(!is_satisfied(setup(&pairs, 0, 0)), "accepted a zero denominator");
+
+        let ratio_bound = max_correlation_ratio_bound();
+        let mut circuit = setup(&pairs, 1, 2);
+        circuit.bounds_correlation_num = ratio_bound;
+        circuit.bounds_correlation_den = ratio_bound + ratio_bound;
+        assert!(!is_satisfied(circuit), "accepted a ratio that's too large");
+
+        let mut circuit = setup(&pairs, 1, 2);
+        circuit.bounds_purchase_price_max = Amount(max_correlation_value_bound());
+        assert!(!is_satisfied(circuit), "accepted a maximum price that's too large");
+
+        let mut circuit = setup(&pairs, 1, 2);
+        circuit.bounds_quantity_max = max_correlation_value_bound();
+        assert!(!is_satisfied(circuit), "accepted a maximum quantity that's too large");
+
+        let mut circuit = setup(&pairs, 1, 2);
+        circuit.bounds_quantity_max = F::from(2u32);
+        assert!(!is_satisfied(circuit), "accepted a quantity above the maximum");
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{
+    amount::{Amount, AmountVar},
     hash::{LeafHash, LeafHashGadget, LeafHashParamsVar},
     merkle::{Leaf, MerkleConfig},
     F, FV,
@@ -8,7 +9,7 @@ use ark_crypto_primitives::{
     crh::{constraints::CRHSchemeGadget, CRHScheme},
     merkle_tree::{Config, DigestConverter},
 };
-use ark_ff::UniformRand;
+use ark_ff::{BigInteger, PrimeField, UniformRand};
 use ark_r1cs_std::{uint8::UInt8, ToBytesGadget};
 use ark_relations::r1cs::SynthesisError;
 use ark_serialize::CanonicalSerialize;
@@ -18,31 +19,36 @@ use rand::Rng;
 // NATIVE IMPLEMENTATIONS
 //
 
-/// A baseball card. The leaves in our tree are card commitments.
+/// One period's purchase: the price paid and how many units were bought at it. The leaves in our
+/// tree are commitments to these.
 #[derive(Clone, CanonicalSerialize)]
 pub struct Data {
-    pub purchase_price: F,
+    pub purchase_price: Amount,
+    /// The number of units bought at `purchase_price`
+    pub quantity: F,
 }
 
 impl Data {
-    /// Commits to `(self.amount, self.serial_num)` using `com_rand` as the commitment randomness.
-    /// Concretely, this computes `Hash(com_rand || amount || nulifier)`
+    /// Commits to `(self.purchase_price, self.quantity)` using `com_rand` as the commitment
+    /// randomness. Concretely, this computes `Hash(DATA_DOMAIN_TAG || com_rand || amount ||
+    /// quantity)`, where `amount` is the purchase price.
     pub fn commit(
         &self,
         leaf_crh_params: &<LeafHash as CRHScheme>::Parameters,
         com_rand: &F,
     ) -> Leaf {
-        // This will be the buffer we feed into the hash function
-        let mut buf = Vec::new();
+        // This will be the buffer we feed into the hash function. It starts with the tag that
+        // sets data commitments apart from bounds commitments.
+        let mut buf = vec![DATA_DOMAIN_TAG];
 
         // Serialize the randomness
         com_rand.serialize_uncompressed(&mut buf).unwrap();
 
-        // Now serialize the card
+        // Now serialize the price and quantity
         self.serialize_uncompressed(&mut buf).unwrap();
 
-        // Now compute Hash(com_rand || amount || nulifier)
-        let claimed_leaf_hash = LeafHash::evaluate(&leaf_crh_params, buf.as_slice()).unwrap();
+        // Now compute Hash(DATA_DOMAIN_TAG || com_rand || amount || quantity)
+        let claimed_leaf_hash = LeafHash::evaluate(leaf_crh_params, buf.as_slice()).unwrap();
 
         <MerkleConfig as Config>::LeafInnerDigestConverter::convert(claimed_leaf_hash)
             .unwrap()
@@ -51,45 +57,587 @@ impl Data {
     }
 }
 
-// Helpful for testing. This lets you generate a random Card.
+/// The domain tag at the start of a data commitment's input
+pub const DATA_DOMAIN_TAG: u8 = 0;
+
+/// The domain tag at the start of a bounds commitment's input. Both kinds of input are the same
+/// length, and their first bytes differ, so no data commitment can be opened as bounds or the
+/// other way around.
+pub const BOUNDS_DOMAIN_TAG: u8 = 1;
+
+/// The bounds every purchase price must be within. Usually these are public, but a confidential
+/// policy can instead be committed to like `Data`, so only the commitment is public.
+#[derive(Clone, CanonicalSerialize)]
+pub struct PriceBounds {
+    pub purchase_price_min: Amount,
+    pub purchase_price_max: Amount,
+}
+
+impl PriceBounds {
+    /// Commits to the bounds using `com_rand` as the commitment randomness. Concretely, this
+    /// computes `Hash(BOUNDS_DOMAIN_TAG || com_rand || min || max)`.
+    pub fn commit(
+        &self,
+        leaf_crh_params: &<LeafHash as CRHScheme>::Parameters,
+        com_rand: &F,
+    ) -> Leaf {
+        let mut buf = vec![BOUNDS_DOMAIN_TAG];
+        com_rand.serialize_uncompressed(&mut buf).unwrap();
+        self.serialize_uncompressed(&mut buf).unwrap();
+
+        let bounds_hash = LeafHash::evaluate(leaf_crh_params, buf.as_slice()).unwrap();
+        <MerkleConfig as Config>::LeafInnerDigestConverter::convert(bounds_hash)
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+}
+
+// Helpful for testing. This lets you generate random Data.
 impl UniformRand for Data {
-    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+    fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
         Data {
-            purchase_price: F::from(500),
+            purchase_price: Amount::from_minor_units(500),
             //purchase_price: F::rand(rng),
+            quantity: F::from(1),
+        }
+    }
+}
+
+/// How an average is rounded to an integer. The analysis circuit proves
+/// `sum + offset = avg * n + r` with `0 <= r < n`, where the offset depends on the rounding mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round down, i.e., `avg = floor(sum / n)`
+    Floor,
+    /// Round to the nearest integer, with halves rounded up, i.e., `avg = floor((sum + n/2) / n)`
+    Nearest,
+    /// Only accept averages that divide evenly, i.e., `r = 0`
+    Exact,
+}
+
+impl RoundingMode {
+    /// The amount added to the sum before dividing by `n`
+    pub fn offset(&self, n: u64) -> u64 {
+        match self {
+            RoundingMode::Nearest => n / 2,
+            RoundingMode::Floor | RoundingMode::Exact => 0,
+        }
+    }
+}
+
+/// Converts a field element to an integer. Returns `None` if the element doesn't fit in a `u128`.
+pub fn field_to_u128(x: &F) -> Option<u128> {
+    let bytes = x.into_bigint().to_bytes_le();
+    if bytes[16..].iter().any(|&b| b != 0) {
+        return None;
+    }
+    Some(u128::from_le_bytes(bytes[..16].try_into().unwrap()))
+}
+
+/// Computes the integer average of the given values using the given rounding mode. This is the
+/// value the analysis circuit proves is within the revealed interval in range-only mode. Returns
+/// `None` if there are no values, if a value is not a small integer, or if the rounding mode is
+/// `Exact` and the average isn't one.
+pub fn average(values: &[Amount], rounding: RoundingMode) -> Option<Amount> {
+    let n = values.len() as u128;
+    if n == 0 {
+        return None;
+    }
+
+    let sum = values
+        .iter()
+        .map(Amount::to_minor_units)
+        .try_fold(0u128, |acc, v| acc.checked_add(v?))?;
+    let shifted_sum = sum + rounding.offset(n as u64) as u128;
+    let (avg, remainder) = (shifted_sum / n, shifted_sum % n);
+
+    if rounding == RoundingMode::Exact && remainder != 0 {
+        return None;
+    }
+
+    Some(Amount(F::from(avg)))
+}
+
+/// Splits `num_periods` periods into `num_groups` groups of consecutive periods of the same size,
+/// e.g., 12 months into 4 quarters or 2 fiscal halves. Each group is the list of its period
+/// indices. Returns `None` if the periods don't divide evenly.
+pub fn even_partition(num_periods: usize, num_groups: usize) -> Option<Vec<Vec<usize>>> {
+    if num_groups == 0 || !num_periods.is_multiple_of(num_groups) {
+        return None;
+    }
+
+    let group_size = num_periods / num_groups;
+    Some(
+        (0..num_groups)
+            .map(|g| (g * group_size..(g + 1) * group_size).collect())
+            .collect(),
+    )
+}
+
+/// Sums the values in each group of the partition. These are the subtotals the analysis circuit
+/// expects, and they add up to the grand total if every value is in exactly one group. Returns
+/// `None` if a group refers to a value that doesn't exist.
+pub fn subtotals(values: &[Amount], partition: &[Vec<usize>]) -> Option<Vec<Amount>> {
+    partition
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .map(|&i| values.get(i).map(|value| value.0))
+                .sum::<Option<F>>()
+                .map(Amount)
+        })
+        .collect()
+}
+
+/// Converts a field element to a signed integer, where `-x` is represented by the field element
+/// `p - x`. Returns `None` if the element doesn't fit in an `i128`.
+pub fn field_to_i128(x: &F) -> Option<i128> {
+    match field_to_u128(x) {
+        Some(v) => i128::try_from(v).ok(),
+        None => i128::try_from(field_to_u128(&-*x)?).ok().map(|v| -v),
+    }
+}
+
+/// The most decimal digits a fixed-point slope can have. The trend circuit's remainder check only
+/// holds over the integers if `10^decimals·N − s·D` can't wrap around the field. With prices below
+/// 2^64 and `n` periods, `|N| < 2^63·n³` and `D < n⁴/12`, and the claimed slope `s` is below
+/// 2^127. Since `10^18 < 2^60`, the difference is then below `2^123·n³ + 2^124·n⁴`, which for any
+/// `n` below 2^30 is under 2^245, far below `p/2 > 2^253`.
+pub const MAX_SLOPE_DECIMALS: u32 = 18;
+
+/// Computes the least-squares slope of the given values against their index `t = 0, 1, ...`, as
+/// the triple `(numerator, denominator, fixed_point)`. The slope is exactly
+/// `numerator / denominator`, where the numerator is `n·Σt·x − Σt·Σx` and the denominator is
+/// `n·Σt² − (Σt)²`. The fixed-point slope is `floor(10^decimals · numerator / denominator)`. The
+/// numerator and fixed-point slope may be negative, and are represented as in `field_to_i128`.
+/// This is what the trend circuit expects. Returns `None` if there are fewer than two values, a
+/// value is not a small integer, or there are more than `MAX_SLOPE_DECIMALS` decimals.
+pub fn linear_trend(values: &[Amount], decimals: u32) -> Option<(F, F, F)> {
+    let n = i128::try_from(values.len()).ok()?;
+    if n < 2 || decimals > MAX_SLOPE_DECIMALS {
+        return None;
+    }
+    let ints = values
+        .iter()
+        .map(|v| i128::try_from(v.to_minor_units()?).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let sum = ints.iter().try_fold(0i128, |acc, &x| acc.checked_add(x))?;
+    let sum_t_x = ints
+        .iter()
+        .zip(0i128..)
+        .try_fold(0i128, |acc, (&x, t)| acc.checked_add(t.checked_mul(x)?))?;
+    let sum_t = n * (n - 1) / 2;
+    let sum_t_sq = (n - 1) * n * (2 * n - 1) / 6;
+
+    let numerator = n.checked_mul(sum_t_x)?.checked_sub(sum_t.checked_mul(sum)?)?;
+    let denominator = n * sum_t_sq - sum_t * sum_t;
+    let scale = 10i128.checked_pow(decimals)?;
+    let fixed_point = numerator.checked_mul(scale)?.div_euclid(denominator);
+
+    Some((F::from(numerator), F::from(denominator), F::from(fixed_point)))
+}
+
+/// Computes the average of every window of `window` consecutive values, e.g., the 3-month rolling
+/// average of monthly prices. This is the vector the moving average circuit expects. Returns
+/// `None` if the window is empty or longer than the series, or if any window average fails to
+/// compute (see `average`).
+pub fn moving_averages(
+    values: &[Amount],
+    window: usize,
+    rounding: RoundingMode,
+) -> Option<Vec<Amount>> {
+    if window == 0 || window > values.len() {
+        return None;
+    }
+    values
+        .windows(window)
+        .map(|window_values| average(window_values, rounding))
+        .collect()
+}
+
+/// Computes the quantity-weighted average price `Σ(q·p) / Σq` of the given data, using the given
+/// rounding mode. This is the value the weighted average circuit expects. Returns `None` if the
+/// total quantity is zero, if a price or quantity is not a small integer, or if the rounding mode
+/// is `Exact` and the average isn't one.
+pub fn weighted_average(data: &[Data], rounding: RoundingMode) -> Option<Amount> {
+    let (total_cost, total_quantity) =
+        data.iter().try_fold((0u128, 0u128), |(cost, quantity), d| {
+            let p = d.purchase_price.to_minor_units()?;
+            let q = field_to_u128(&d.quantity)?;
+            Some((cost.checked_add(q.checked_mul(p)?)?, quantity.checked_add(q)?))
+        })?;
+    if total_quantity == 0 {
+        return None;
+    }
+
+    // Rounding to nearest is floor((2·cost + quantity) / (2·quantity)), which avoids halving the
+    // total quantity
+    let (numerator, denominator) = match rounding {
+        RoundingMode::Nearest => (
+            total_cost.checked_mul(2)?.checked_add(total_quantity)?,
+            total_quantity.checked_mul(2)?,
+        ),
+        RoundingMode::Floor | RoundingMode::Exact => (total_cost, total_quantity),
+    };
+    let (avg, remainder) = (numerator / denominator, numerator % denominator);
+
+    if rounding == RoundingMode::Exact && remainder != 0 {
+        return None;
+    }
+
+    Some(Amount(F::from(avg)))
+}
+
+/// Computes how the average of `current` compares to the average of `base`, in percent, rounded
+/// down. So 100 means the average didn't change, 103 means it rose by 3%, and 95 means it fell by
+/// 5%. The growth circuit accepts any bounds `[min, max]` that contain the exact ratio, so
+/// `[g, g + 1]` always works for the value `g` returned here. Returns `None` if either series is
+/// empty, if a value is not a small integer, or if the base average is zero.
+pub fn growth_percent(base: &[Amount], current: &[Amount]) -> Option<F> {
+    let sum = |values: &[Amount]| {
+        values
+            .iter()
+            .map(Amount::to_minor_units)
+            .try_fold(0u128, |acc, v| acc.checked_add(v?))
+    };
+    let (base_sum, current_sum) = (sum(base)?, sum(current)?);
+    let (base_len, current_len) = (base.len() as u128, current.len() as u128);
+    if base_sum == 0 || current_len == 0 {
+        return None;
+    }
+
+    // avg_c / avg_b = (S_c · n_b) / (S_b · n_c)
+    let numerator = current_sum.checked_mul(base_len)?.checked_mul(100)?;
+    let denominator = base_sum.checked_mul(current_len)?;
+    Some(F::from(numerator / denominator))
+}
+
+/// Computes how far the furthest value is from the mean, in percent of the mean absolute
+/// deviation, rounded up. So 300 means no value is more than 3 times the MAD away from the mean.
+/// With `n` values summing to `S`, value `x_i` is `d_i / n` from the mean, where
+/// `d_i = |n·x_i − S|`, and the MAD is `Σd_j / n²`, so this is `100 · n · max d_i / Σd_j`. The
+/// volatility circuit accepts any bound at least this large. Returns 0 if every value is the mean,
+/// and `None` if there are no values or a value is not a small integer.
+pub fn deviation_percent(values: &[Amount]) -> Option<F> {
+    let values = values
+        .iter()
+        .map(|v| i128::try_from(v.to_minor_units()?).ok())
+        .collect::<Option<Vec<i128>>>()?;
+    if values.is_empty() {
+        return None;
+    }
+
+    let n = values.len() as i128;
+    let sum = values.iter().try_fold(0i128, |acc, &v| acc.checked_add(v))?;
+    let deviations = values
+        .iter()
+        .map(|&v| Some(v.checked_mul(n)?.checked_sub(sum)?.unsigned_abs()))
+        .collect::<Option<Vec<u128>>>()?;
+    let total_deviation = deviations.iter().try_fold(0u128, |acc, &d| acc.checked_add(d))?;
+    if total_deviation == 0 {
+        return Some(F::from(0u32));
+    }
+
+    let max_deviation = deviations.into_iter().max()?;
+    let numerator = max_deviation.checked_mul(n as u128)?.checked_mul(100)?;
+    Some(F::from(numerator.div_ceil(total_deviation)))
+}
+
+/// Computes the largest change from one value to the next, in percent of the earlier value,
+/// rounded up. So 10 means no value is more than 10% above or below the one before it. The
+/// volatility circuit accepts any bound at least this large. Returns 0 if there are fewer than two
+/// values, and `None` if a value is not a small integer, or a value changes from zero.
+pub fn change_percent(values: &[Amount]) -> Option<F> {
+    let values = values
+        .iter()
+        .map(Amount::to_minor_units)
+        .collect::<Option<Vec<u128>>>()?;
+
+    let mut max_change = 0;
+    for pair in values.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        let change = prev.abs_diff(next).checked_mul(100)?;
+        if change == 0 {
+            continue;
+        }
+        if prev == 0 {
+            return None;
+        }
+        max_change = max_change.max(change.div_ceil(prev));
+    }
+    Some(F::from(max_change))
+}
+
+/// The direction a monotonic series moves in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Monotonicity {
+    /// Every value is at least the one before it, e.g., an indexed rent
+    NonDecreasing,
+    /// Every value is at most the one before it
+    NonIncreasing,
+}
+
+/// Computes the largest step from one value to the next, if the values move in the given
+/// direction. The monotonic circuit accepts any maximum step at least this large. Returns 0 if
+/// there are fewer than two values, and `None` if a value is not a small integer or the values
+/// ever move the other way.
+pub fn max_step(values: &[Amount], direction: Monotonicity) -> Option<Amount> {
+    let values = values
+        .iter()
+        .map(Amount::to_minor_units)
+        .collect::<Option<Vec<u128>>>()?;
+
+    let mut max_step = 0;
+    for pair in values.windows(2) {
+        let step = match direction {
+            Monotonicity::NonDecreasing => pair[1].checked_sub(pair[0])?,
+            Monotonicity::NonIncreasing => pair[0].checked_sub(pair[1])?,
+        };
+        max_step = max_step.max(step);
+    }
+    Some(Amount(F::from(max_step)))
+}
+
+/// Counts how many of the given values fall into each bucket, where the buckets are separated by
+/// the given edges. With edges `e_1 < ... < e_k` there are `k + 1` buckets: bucket 0 holds the
+/// values below `e_1`, bucket j holds the values in `[e_j, e_{j+1})`, and bucket k holds the
+/// values from `e_k` up. This is the vector the histogram circuit expects. Returns `None` if the
+/// edges aren't strictly increasing.
+pub fn histogram(values: &[Amount], edges: &[Amount]) -> Option<Vec<F>> {
+    if edges.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return None;
+    }
+
+    let mut counts = vec![0u64; edges.len() + 1];
+    for value in values {
+        let bucket = edges.iter().filter(|&edge| edge <= value).count();
+        counts[bucket] += 1;
+    }
+    Some(counts.into_iter().map(F::from).collect())
+}
+
+/// Computes the Pearson correlation between the purchase prices and the quantities of the given
+/// data, in floating point. This is only an estimate, meant for picking a bound that the
+/// correlation circuit will accept. Returns `None` if a value is not a small integer, or if either
+/// series is constant.
+pub fn correlation(data: &[Data]) -> Option<f64> {
+    let n = data.len() as f64;
+    let pairs = data
+        .iter()
+        .map(|d| {
+            let x = d.purchase_price.to_minor_units()? as f64;
+            let y = field_to_u128(&d.quantity)? as f64;
+            Some((x, y))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let (sum_x, sum_y) = pairs.iter().fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+    let (cov, var_x, var_y) = pairs.iter().fold((0.0, 0.0, 0.0), |(c, vx, vy), (x, y)| {
+        let (dx, dy) = (x - mean_x, y - mean_y);
+        (c + dx * dy, vx + dx * dx, vy + dy * dy)
+    });
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+/// One good in a basket of goods, as of the public base period
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BasketGood {
+    /// The price of the good in the base period
+    pub base_price: Amount,
+    /// How much of the good the basket holds. Only the Laspeyres index uses this.
+    pub weight: F,
+}
+
+/// How a price index weighs the goods in a basket
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexFormula {
+    /// Weigh every good by its fixed basket weight, i.e., `Σ w·p / Σ w·p_0`
+    Laspeyres,
+    /// Weigh every good by the quantity bought in the current period, i.e., `Σ q·p / Σ q·p_0`
+    Paasche,
+}
+
+impl IndexFormula {
+    /// Returns the numerator and denominator of this index for the given basket and the data of
+    /// one period, with one `Data` per good. Returns `None` if the lengths don't match or a value
+    /// is not a small integer.
+    fn terms(&self, basket: &[BasketGood], current: &[Data]) -> Option<(u128, u128)> {
+        if basket.len() != current.len() {
+            return None;
         }
+        basket.iter().zip(current).try_fold((0u128, 0u128), |(num, den), (good, data)| {
+            let weight = match self {
+                IndexFormula::Laspeyres => field_to_u128(&good.weight)?,
+                IndexFormula::Paasche => field_to_u128(&data.quantity)?,
+            };
+            let price = data.purchase_price.to_minor_units()?;
+            let base_price = good.base_price.to_minor_units()?;
+            Some((
+                num.checked_add(weight.checked_mul(price)?)?,
+                den.checked_add(weight.checked_mul(base_price)?)?,
+            ))
+        })
+    }
+}
+
+/// Computes the price index of one period relative to the base basket, where the base period is
+/// 100. It's in fixed point with the given number of decimal digits and rounded down, so 104.25
+/// with 2 decimals is 10425. This is the value the price index circuit expects. Returns `None` if
+/// the base basket costs nothing, or see `IndexFormula::terms`.
+pub fn price_index(
+    basket: &[BasketGood],
+    current: &[Data],
+    formula: IndexFormula,
+    decimals: u32,
+) -> Option<F> {
+    let (numerator, denominator) = formula.terms(basket, current)?;
+    if denominator == 0 {
+        return None;
+    }
+    let scale = 10u128.checked_pow(decimals)?.checked_mul(100)?;
+    Some(F::from(numerator.checked_mul(scale)? / denominator))
+}
+
+/// A statistic about the position of a value in the sorted data, rather than its size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderStatistic {
+    /// The k-th smallest value, counting from 0. So `Kth(0)` is the minimum.
+    Kth(usize),
+    /// Twice the median. This is the sum of the two middle values, which is an integer even when
+    /// there are an even number of values. When there are an odd number of values, both middle
+    /// values are the same one.
+    DoubleMedian,
+}
+
+impl OrderStatistic {
+    /// Returns the positions in `n` sorted values that are added together to get this statistic.
+    /// For `Kth` there is only one position, and the statistic is just that value. Returns `None`
+    /// if there aren't enough values, e.g., if there are none at all.
+    pub fn sorted_positions(&self, n: usize) -> Option<Vec<usize>> {
+        let positions = match *self {
+            OrderStatistic::Kth(k) => vec![k],
+            OrderStatistic::DoubleMedian => vec![n.checked_sub(1)? / 2, n / 2],
+        };
+        positions.iter().all(|&pos| pos < n).then_some(positions)
+    }
+}
+
+/// Computes the given order statistic of the given values. This is the value the order statistic
+/// circuit expects. Returns `None` if there aren't enough values.
+pub fn order_statistic(values: &[Amount], statistic: OrderStatistic) -> Option<Amount> {
+    let mut sorted = values.to_vec();
+    sorted.sort();
+
+    let positions = statistic.sorted_positions(sorted.len())?;
+    Some(Amount(positions.iter().map(|&pos| sorted[pos].0).sum()))
+}
+
+/// Computes `n·Σx² − (Σx)²` over the given values, i.e., `n²` times their population variance.
+/// This is the value the variance circuit expects. Returns `None` if a value is not a small
+/// integer or the result doesn't fit in a `u128`.
+pub fn scaled_variance(values: &[Amount]) -> Option<F> {
+    let n = values.len() as u128;
+    let ints = values.iter().map(Amount::to_minor_units).collect::<Option<Vec<_>>>()?;
+
+    let sum = ints.iter().try_fold(0u128, |acc, &v| acc.checked_add(v))?;
+    let sum_sq = ints
+        .iter()
+        .try_fold(0u128, |acc, &v| acc.checked_add(v.checked_mul(v)?))?;
+
+    // By Cauchy-Schwarz this never underflows
+    let scaled = n.checked_mul(sum_sq)? - sum.checked_mul(sum)?;
+    Some(F::from(scaled))
+}
+
+/// Computes `floor(sqrt(x))` for a field element that is a small integer. Returns `None` if it
+/// isn't.
+pub fn floor_sqrt(x: &F) -> Option<F> {
+    let x = field_to_u128(x)?;
+    if x < 2 {
+        return Some(F::from(x));
+    }
+
+    // Newton's method, starting from a guess that's above the root
+    let mut root = x / 2 + 1;
+    let mut next = (root + x / root) / 2;
+    while next < root {
+        root = next;
+        next = (root + x / root) / 2;
     }
+    Some(F::from(root))
 }
 
 //
 // R1CS IMPLEMENTATIONS
 //
 
-/// R1CS representation of Card
+/// R1CS representation of Data
 pub struct DataVar {
-    pub amount: FV,
+    pub amount: AmountVar,
+    pub quantity: FV,
 }
 
-/// Defines a way to serialize a CardVar to bytes. This is only works if it is identical to the
-/// `impl CanonicalSerialize for Card` serialization.
+/// Defines a way to serialize a DataVar to bytes. This only works if it is identical to the
+/// `impl CanonicalSerialize for Data` serialization.
 impl ToBytesGadget<F> for DataVar {
     fn to_bytes(&self) -> Result<Vec<UInt8<F>>, SynthesisError> {
-        // Serialize self.amount then self.serial_num
-        Ok([self.amount.to_bytes()?].concat())
+        // Serialize self.amount then self.quantity
+        Ok([self.amount.to_bytes()?, self.quantity.to_bytes()?].concat())
     }
 }
 
 impl DataVar {
-    /// Commits to this card using the given commitment randomness. Concretely, this computes
-    /// `Hash(com_rand || self.amount || self.serial_num)`.
+    /// Commits to this data using the given commitment randomness. Concretely, this computes
+    /// `Hash(DATA_DOMAIN_TAG || com_rand || self.amount || self.quantity)`, matching
+    /// `Data::commit`.
+    pub fn commit(
+        &self,
+        hash_params: &LeafHashParamsVar,
+        com_rand: &FV,
+    ) -> Result<Vec<UInt8<F>>, SynthesisError> {
+        let tag_bytes = vec![UInt8::constant(DATA_DOMAIN_TAG)];
+        let com_rand_bytes = com_rand.to_bytes()?;
+        let data_bytes = self.to_bytes()?;
+        let input = [tag_bytes, com_rand_bytes, data_bytes].concat();
+        let hash = LeafHashGadget::evaluate(hash_params, &input)?;
+        hash.to_bytes()
+    }
+}
+
+/// R1CS representation of PriceBounds
+pub struct PriceBoundsVar {
+    pub purchase_price_min: AmountVar,
+    pub purchase_price_max: AmountVar,
+}
+
+/// Serializes the bounds identically to the `impl CanonicalSerialize for PriceBounds`
+/// serialization
+impl ToBytesGadget<F> for PriceBoundsVar {
+    fn to_bytes(&self) -> Result<Vec<UInt8<F>>, SynthesisError> {
+        Ok([self.purchase_price_min.to_bytes()?, self.purchase_price_max.to_bytes()?].concat())
+    }
+}
+
+impl PriceBoundsVar {
+    /// Commits to the bounds using the given commitment randomness. This matches
+    /// `PriceBounds::commit`.
     pub fn commit(
         &self,
         hash_params: &LeafHashParamsVar,
         com_rand: &FV,
     ) -> Result<Vec<UInt8<F>>, SynthesisError> {
+        let tag_bytes = vec![UInt8::constant(BOUNDS_DOMAIN_TAG)];
         let com_rand_bytes = com_rand.to_bytes()?;
-        let card_bytes = self.to_bytes()?;
-        let hash = LeafHashGadget::evaluate(&hash_params, &[com_rand_bytes, card_bytes].concat())?;
+        let bounds_bytes = self.to_bytes()?;
+        let input = [tag_bytes, com_rand_bytes, bounds_bytes].concat();
+        let hash = LeafHashGadget::evaluate(hash_params, &input)?;
         hash.to_bytes()
     }
 }
@@ -0,0 +1,308 @@
+use crate::{
+    amount::Amount,
+    constraints::{
+        data_public_inputs, enforce_price_bounds, open_periods, public_coms, Period, PublicInputs,
+    },
+    hash::{LeafHash, TwoToOneHash},
+    range::{enforce_bit_width, enforce_cmp_small, VALUE_BITS},
+    F, FV,
+};
+
+use ark_crypto_primitives::crh::{CRHScheme, TwoToOneCRHScheme};
+use ark_ff::ToConstraintField;
+use ark_r1cs_std::{alloc::AllocVar, fields::FieldVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use core::cmp::Ordering;
+
+/// A ZK circuit that proves "the average purchase price rose by between X% and Y% from the base
+/// year to the current year", without revealing any prices. It opens two series of commitments,
+/// e.g., 12 months of last year and 12 months of this year, made with the same hash parameters.
+///
+/// The bounds are ratios in percent, so bounds of 103 and 110 mean growth between 3% and 10%, and
+/// a bound below 100 means a fall. To avoid division, the circuit compares
+/// `100 · avg_current` against `bound · avg_base` by cross-multiplying, i.e.,
+/// `100 · S_c · n_b` against `bound · S_b · n_c`, where `S` and `n` are the sum and the number of
+/// periods of each series. Both series are always opened against public commitments.
+#[derive(Clone)]
+pub struct GrowthCircuit {
+    // These are constants that will be embedded into the circuit. They describe how the hash
+    // function works. Don't worry about this.
+    pub leaf_crh_params: <LeafHash as CRHScheme>::Parameters,
+    pub two_to_one_crh_params: <TwoToOneHash as TwoToOneCRHScheme>::Parameters,
+
+    /// The data for every period of the base year. See `AnalysisCircuit::periods`.
+    pub base_periods: Vec<Period>,
+    /// The data for every period of the current year. This doesn't need to have as many periods as
+    /// the base year.
+    pub current_periods: Vec<Period>,
+
+    // The claimed growth, as a range of ratios in percent. Use `econ_data::growth_percent` to find
+    // a range that holds. Both ends must be below `max_value_bound()`, and the minimum can't be
+    // above the maximum.
+    pub bounds_growth_percent_min: F,
+    pub bounds_growth_percent_max: F,
+
+    // The analysis bounds, which apply to the prices of both years. The maximum must be below
+    // `max_value_bound()`.
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+/// The claimed growth range of a `GrowthCircuit`, along with the price bounds it was computed
+/// under. The prover publishes these next to the proof.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct GrowthOutputs {
+    pub bounds_growth_percent_min: F,
+    pub bounds_growth_percent_max: F,
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+/// Serializes the outputs in the same order the circuit allocates them
+impl ToConstraintField<F> for GrowthOutputs {
+    fn to_field_elements(&self) -> Option<Vec<F>> {
+        Some(vec![
+            self.bounds_growth_percent_min,
+            self.bounds_growth_percent_max,
+            self.bounds_purchase_price_min.0,
+            self.bounds_purchase_price_max.0,
+        ])
+    }
+}
+
+/// The public statement proven by a `GrowthCircuit`. The verifier builds this from both years'
+/// commitments and the claimed growth range, then converts it to the public-input vector.
+#[derive(Clone)]
+pub struct GrowthStatement {
+    /// The data commitment for every period of the base year, in order
+    pub base_coms: Vec<Vec<u8>>,
+    /// The data commitment for every period of the current year, in order
+    pub current_coms: Vec<Vec<u8>>,
+    pub outputs: GrowthOutputs,
+}
+
+/// Serializes the statement in the same order the circuit allocates its public inputs
+impl ToConstraintField<F> for GrowthStatement {
+    fn to_field_elements(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                data_public_inputs(None, &self.base_coms)?,
+                data_public_inputs(None, &self.current_coms)?,
+                self.outputs.to_field_elements()?,
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl GrowthCircuit {
+    /// Returns the public statement this circuit proves
+    pub fn statement(&self) -> GrowthStatement {
+        GrowthStatement {
+            base_coms: public_coms(None, &self.base_periods),
+            current_coms: public_coms(None, &self.current_periods),
+            outputs: GrowthOutputs {
+                bounds_growth_percent_min: self.bounds_growth_percent_min,
+                bounds_growth_percent_max: self.bounds_growth_percent_max,
+                bounds_purchase_price_min: self.bounds_purchase_price_min,
+                bounds_purchase_price_max: self.bounds_purchase_price_max,
+            },
+        }
+    }
+}
+
+impl PublicInputs for GrowthCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        self.statement().to_field_elements()
+    }
+}
+
+impl ConstraintSynthesizer<F> for GrowthCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // Allocate and open the data for every period of both years, exactly like AnalysisCircuit
+        // does. The base year's commitments come first.
+        let base_data_vars = open_periods(
+            cs.clone(),
+            &self.leaf_crh_params,
+            &self.two_to_one_crh_params,
+            &self.base_periods,
+            None,
+        )?;
+        let current_data_vars = open_periods(
+            cs.clone(),
+            &self.leaf_crh_params,
+            &self.two_to_one_crh_params,
+            &self.current_periods,
+            None,
+        )?;
+
+        // create inputs for the claimed growth range
+        let bounds_growth_percent_min = FV::new_input(ns!(cs, "bounds growth min"), || {
+            Ok(&self.bounds_growth_percent_min)
+        })?;
+        let bounds_growth_percent_max = FV::new_input(ns!(cs, "bounds growth max"), || {
+            Ok(&self.bounds_growth_percent_max)
+        })?;
+
+        // create inputs for the price bounds, and check the prices of both years are within them
+        let prices: Vec<FV> = base_data_vars
+            .iter()
+            .chain(current_data_vars.iter())
+            .map(|data_var| data_var.amount.0.clone())
+            .collect();
+        let _ = enforce_price_bounds(
+            cs.clone(),
+            &prices,
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // growth bounds constraints. With prices and growth bounds below 2^64, both sides of the
+        // comparisons below stay far from the field size, so nothing wraps around. Both growth
+        // bounds need this, since both are multiplied into the comparisons.
+        enforce_bit_width(cs.clone(), &bounds_growth_percent_min, VALUE_BITS)?;
+        enforce_bit_width(cs.clone(), &bounds_growth_percent_max, VALUE_BITS)?;
+        enforce_cmp_small(
+            cs.clone(),
+            &bounds_growth_percent_min,
+            &bounds_growth_percent_max,
+            Ordering::Less,
+            true,
+            VALUE_BITS,
+        )?;
+
+        // compute the sum of each year. These are linear combinations, so they're free.
+        let base_sum: FV = base_data_vars.iter().map(|data_var| &data_var.amount.0).sum();
+        let current_sum: FV = current_data_vars.iter().map(|data_var| &data_var.amount.0).sum();
+        let base_len = F::from(self.base_periods.len() as u64);
+        let current_len = F::from(self.current_periods.len() as u64);
+
+        // Growth is undefined if the base average is zero
+        base_sum.enforce_cmp(&FV::zero(), Ordering::Greater, false)?;
+
+        // check the growth range. The ratio avg_c / avg_b, in percent, is
+        // (100 · S_c · n_b) / (S_b · n_c), and we check min <= ratio <= max by multiplying through
+        // by the positive denominator.
+        let scaled_current = current_sum * (base_len * F::from(100u32));
+        let scaled_base = base_sum * current_len;
+        let lower = &bounds_growth_percent_min * &scaled_base;
+        let upper = &bounds_growth_percent_max * &scaled_base;
+        scaled_current.enforce_cmp(&lower, Ordering::Greater, true)?;
+        scaled_current.enforce_cmp(&upper, Ordering::Less, true)?;
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constraints::max_value_bound,
+        econ_data::growth_percent,
+        util::{gen_test_periods, test_harness::is_satisfied},
+    };
+
+    use ark_ff::One;
+
+    // Sets up a growth circuit over the given prices, claiming growth between the given percents
+    fn setup(base_prices: &[u64], current_prices: &[u64], min: u64, max: u64) -> GrowthCircuit {
+        let mut rng = ark_std::test_rng();
+        let leaf_crh_params = <LeafHash as CRHScheme>::setup(&mut rng).unwrap();
+        let two_to_one_crh_params = <TwoToOneHash as TwoToOneCRHScheme>::setup(&mut rng).unwrap();
+
+        let to_amounts = |prices: &[u64]| {
+            prices.iter().map(|&p| Amount::from_minor_units(p)).collect::<Vec<Amount>>()
+        };
+        let base_periods = gen_test_periods(&leaf_crh_params, &to_amounts(base_prices), &mut rng);
+        let current_periods =
+            gen_test_periods(&leaf_crh_params, &to_amounts(current_prices), &mut rng);
+
+        GrowthCircuit {
+            leaf_crh_params,
+            two_to_one_crh_params,
+            base_periods,
+            current_periods,
+            bounds_growth_percent_min: F::from(min),
+            bounds_growth_percent_max: F::from(max),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+        }
+    }
+
+    // Range test: The base year averages 200 and the current year averages 220, so prices rose by
+    // exactly 10%. Any range containing 110 is accepted, including the range of just 110, and
+    // ranges that don't are rejected. The current year has fewer periods, which the
+    // cross-multiplication accounts for, since the sums alone would give 660/800 = 82.5%.
+    #[test]
+    fn growth_range() {
+        let base = [100, 200, 300, 200];
+        let current = [210, 220, 230];
+        let to_amounts = |prices: &[u64]| {
+            prices.iter().map(|&p| Amount::from_minor_units(p)).collect::<Vec<Amount>>()
+        };
+        assert_eq!(
+            growth_percent(&to_amounts(&base), &to_amounts(&current)),
+            Some(F::from(110u32))
+        );
+
+        for (min, max) in [(110, 110), (103, 110), (110, 120), (0, 1000)] {
+            let circuit = setup(&base, &current, min, max);
+            assert!(is_satisfied(circuit), "rejected growth between {min}% and {max}%");
+        }
+        for (min, max) in [(111, 120), (100, 109), (82, 83), (120, 100)] {
+            let circuit = setup(&base, &current, min, max);
+            assert!(!is_satisfied(circuit), "accepted growth between {min}% and {max}%");
+        }
+    }
+
+    // Fall test: Prices falling from an average of 200 to 190 is 95% of the base, which is below
+    // 100 but still a positive ratio. Prices falling to nothing at all is 0%.
+    #[test]
+    fn growth_fall() {
+        let circuit = setup(&[100, 200, 300, 200], &[190, 190, 190], 95, 95);
+        assert!(is_satisfied(circuit), "rejected a fall in prices");
+
+        let circuit = setup(&[100, 200, 300, 200], &[0, 0], 0, 0);
+        assert!(is_satisfied(circuit), "rejected a fall to zero");
+        let circuit = setup(&[100, 200, 300, 200], &[0, 0], 1, 100);
+        assert!(!is_satisfied(circuit), "accepted a fall to zero as positive growth");
+    }
+
+    // Zero base test: Growth from a base year that sums to zero is undefined, so no range is
+    // accepted, not even one that holds for any ratio
+    #[test]
+    fn growth_zero_base() {
+        let circuit = setup(&[0, 0, 0], &[100, 100, 100], 0, 1000);
+        assert!(!is_satisfied(circuit), "accepted growth from a zero base");
+    }
+
+    // Wraparound test: A growth bound that's large enough to wrap around the field is rejected, at
+    // either end of the range
+    #[test]
+    fn growth_bound_wraparound() {
+        let mut circuit = setup(&[100, 200, 300, 200], &[210, 220, 230], 103, 110);
+        circuit.bounds_growth_percent_max = max_value_bound();
+        assert!(!is_satisfied(circuit), "accepted a growth bound that's too large");
+
+        // The current side is 100·660·4 = 264000 and the base side is 800·3 = 2400. A minimum of
+        // 263999/2400 in the field is huge, but times 2400 it wraps around to just below 264000.
+        let mut circuit = setup(&[100, 200, 300, 200], &[210, 220, 230], 103, 110);
+        circuit.bounds_growth_percent_min = F::from(263999u32) / F::from(2400u32);
+        circuit.bounds_growth_percent_max = max_value_bound() - F::one();
+        assert!(!is_satisfied(circuit), "accepted a minimum growth that wraps around");
+    }
+}
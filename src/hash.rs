@@ -11,13 +11,13 @@ pub type TwoToOneHash = pedersen::TwoToOneCRH<Jubjub, TwoToOneWindow>;
 pub type LeafHashParams = <LeafHash as CRHScheme>::Parameters;
 pub type TwoToOneHashParams = <TwoToOneHash as TwoToOneCRHScheme>::Parameters;
 
-// We use the leaf hash for card commitments as well. So it needs to handle inputs of 256*3-bits,
-// or 96 bytes
+// We use the leaf hash for data and bounds commitments as well. So it needs to handle inputs of
+// a domain tag byte followed by 256*3 bits, or 97 bytes in all
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct LeafWindow;
 impl pedersen::Window for LeafWindow {
     const WINDOW_SIZE: usize = 6;
-    const NUM_WINDOWS: usize = 128;
+    const NUM_WINDOWS: usize = 130;
 }
 
 // `WINDOW_SIZE * NUM_WINDOWS` > 2 * 512 bits = enough for hashing two outputs. Affine curve points
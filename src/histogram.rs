@@ -0,0 +1,239 @@
+use crate::{
+    amount::{Amount, AmountVar},
+    constraints::{CommittedPeriods, PublicInputs},
+    range::{enforce_bit_width, enforce_cmp_small, is_cmp_small, VALUE_BITS},
+    F, FV,
+};
+
+use ark_r1cs_std::{alloc::AllocVar, boolean::Boolean, eq::EqGadget, fields::FieldVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// A ZK circuit that proves how many of the committed purchase prices fall into each price band,
+/// e.g., how many months were below 300, between 300 and 600, and from 600 up, without revealing
+/// the prices. This generalizes the single `[min, max]` band of `AnalysisCircuit`.
+///
+/// The bands are given by public edges `e_1 < ... < e_k`, which make `k + 1` buckets. Bucket 0
+/// holds the prices below `e_1`, bucket j holds the prices in `[e_j, e_{j+1})`, and bucket k holds
+/// the prices from `e_k` up. See `econ_data::histogram`.
+#[derive(Clone)]
+pub struct HistogramCircuit {
+    /// The committed data for every period, and the ledger it's in, if any.
+    pub data: CommittedPeriods,
+
+    // The analysis outputs
+    /// The number of prices in each bucket, in order. There is one more count than there are
+    /// edges. Use `econ_data::histogram` to compute them.
+    pub output_bucket_counts: Vec<F>,
+
+    // The analysis bounds
+    /// The edges between the buckets, in strictly increasing order and below `max_value_bound()`.
+    /// The number of edges is part of the circuit's shape, but their values are public inputs.
+    pub bounds_bucket_edges: Vec<Amount>,
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+impl PublicInputs for HistogramCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                self.output_bucket_counts.clone(),
+                self.bounds_bucket_edges.iter().map(|edge| edge.0).collect(),
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for HistogramCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // Every bucket needs exactly one claimed count
+        if self.output_bucket_counts.len() != self.bounds_bucket_edges.len() + 1 {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // allocate and open the committed data, and check every price is within the bounds
+        let (data_purchase_prices, _) = self.data.open_prices_within_bounds(
+            cs.clone(),
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        // create inputs for the result values
+        let output_bucket_counts = self
+            .output_bucket_counts
+            .iter()
+            .map(|count| FV::new_input(ns!(cs, "bucket count result"), || Ok(count)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // create inputs for the threshold values
+        let bounds_bucket_edges = self
+            .bounds_bucket_edges
+            .iter()
+            .map(|edge| {
+                AmountVar::new_input(ns!(cs, "bounds bucket edge"), || Ok(edge)).map(|edge| edge.0)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // The edges are range checked like the prices, so comparing them is cheap. They must be
+        // strictly increasing, otherwise the buckets overlap.
+        for edge in &bounds_bucket_edges {
+            enforce_bit_width(cs.clone(), edge, VALUE_BITS)?;
+        }
+        for pair in bounds_bucket_edges.windows(2) {
+            enforce_cmp_small(cs.clone(), &pair[0], &pair[1], Ordering::Less, false, VALUE_BITS)?;
+        }
+
+        // Put every price in its bucket. For each edge we compute the bit "price >= edge". The
+        // price is in bucket j iff it's at least the edge below the bucket and not at least the
+        // edge above it. The lowest bucket has no edge below it, and the highest has none above.
+        let mut computed_counts = vec![FV::zero(); output_bucket_counts.len()];
+        for data_purchase_price in &data_purchase_prices {
+            let at_least_edge = bounds_bucket_edges
+                .iter()
+                .map(|edge| {
+                    is_cmp_small(
+                        cs.clone(),
+                        data_purchase_price,
+                        edge,
+                        Ordering::Greater,
+                        true,
+                        VALUE_BITS,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let in_bucket = (0..computed_counts.len())
+                .map(|j| {
+                    let above_lower = match j {
+                        0 => Boolean::TRUE,
+                        _ => at_least_edge[j - 1].clone(),
+                    };
+                    let above_upper = at_least_edge.get(j).cloned().unwrap_or(Boolean::FALSE);
+                    above_lower.and(&above_upper.not())
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // CHECK: the price is in exactly one bucket
+            let num_buckets = in_bucket
+                .iter()
+                .fold(FV::zero(), |acc, bit| acc + FV::from(bit.clone()));
+            num_buckets.enforce_equal(&FV::one())?;
+
+            for (count, bit) in computed_counts.iter_mut().zip(in_bucket) {
+                *count += FV::from(bit);
+            }
+        }
+
+        // check bucket counts
+        for (computed_count, output_count) in computed_counts.iter().zip(&output_bucket_counts) {
+            computed_count.enforce_equal(output_count)?;
+        }
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constraints::max_value_bound,
+        econ_data::histogram,
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Sets up a legitimate histogram circuit over the given prices and bucket edges
+    fn setup(prices: &[u64], edges: &[u64]) -> HistogramCircuit {
+        let data = gen_test_committed_prices(prices);
+        let prices = data.purchase_prices();
+        let edges: Vec<Amount> = edges.iter().map(|&edge| Amount::from_minor_units(edge)).collect();
+
+        HistogramCircuit {
+            data,
+            output_bucket_counts: histogram(&prices, &edges)
+                .unwrap_or_else(|| vec![F::from(0u32); edges.len() + 1]),
+            bounds_bucket_edges: edges,
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+        }
+    }
+
+    // Edge test: With edges 300 and 600, a price exactly on an edge goes in the bucket above it,
+    // and a price one below goes in the bucket below it. So 299 and 100 are below 300, 300 and
+    // 599 are in [300, 600), and 600 and 900 are from 600 up. Moving any one price to a
+    // neighboring bucket in the claimed counts is rejected.
+    #[test]
+    fn histogram_on_edges() {
+        let circuit = setup(&[599, 100, 600, 300, 900, 299], &[300, 600]);
+        assert_eq!(circuit.output_bucket_counts, [2u32, 2, 2].map(F::from));
+        assert!(is_satisfied(circuit.clone()), "rejected prices on the edges");
+
+        for bad_counts in [[3u32, 1, 2], [1, 3, 2], [2, 3, 1], [2, 1, 3]] {
+            let mut bad_circuit = circuit.clone();
+            bad_circuit.output_bucket_counts = bad_counts.map(F::from).to_vec();
+            assert!(!is_satisfied(bad_circuit), "accepted {bad_counts:?} as bucket counts");
+        }
+    }
+
+    // Empty bucket test: Every price can land in one bucket, and a single edge makes two buckets.
+    // The counts must still add up to the number of prices, so one can't be dropped or repeated.
+    #[test]
+    fn histogram_empty_buckets() {
+        let circuit = setup(&[100, 200, 250], &[300]);
+        assert_eq!(circuit.output_bucket_counts, [3u32, 0].map(F::from));
+        assert!(is_satisfied(circuit.clone()), "rejected an empty top bucket");
+
+        for bad_counts in [[2u32, 0], [3, 1]] {
+            let mut bad_circuit = circuit.clone();
+            bad_circuit.output_bucket_counts = bad_counts.map(F::from).to_vec();
+            assert!(!is_satisfied(bad_circuit), "accepted {bad_counts:?} as bucket counts");
+        }
+
+        let circuit = setup(&[700, 800], &[300, 600]);
+        assert_eq!(circuit.output_bucket_counts, [0u32, 0, 2].map(F::from));
+        assert!(is_satisfied(circuit), "rejected empty lower buckets");
+    }
+
+    // Edge order test: Decreasing or repeated edges would put a price in zero or two buckets, and
+    // an edge past 2^64 would make the cheap comparisons meaningless. The wrong number of counts
+    // doesn't make a circuit at all.
+    #[test]
+    fn histogram_bad_edges() {
+        let circuit = setup(&[599, 100, 600, 300, 900, 299], &[300, 600]);
+
+        for bad_edges in [[600u64, 300], [300, 300]] {
+            let mut bad_circuit = circuit.clone();
+            bad_circuit.bounds_bucket_edges = bad_edges.map(Amount::from_minor_units).to_vec();
+            assert!(!is_satisfied(bad_circuit), "accepted {bad_edges:?} as bucket edges");
+        }
+
+        let mut bad_circuit = circuit.clone();
+        bad_circuit.bounds_bucket_edges[1] = Amount(max_value_bound());
+        bad_circuit.output_bucket_counts = [2u32, 4, 0].map(F::from).to_vec();
+        assert!(!is_satisfied(bad_circuit), "accepted an edge that's too large");
+
+        let mut bad_circuit = circuit;
+        bad_circuit.output_bucket_counts.pop();
+        let cs = ConstraintSystem::new_ref();
+        assert!(bad_circuit.generate_constraints(cs).is_err());
+    }
+}
@@ -1,9 +1,24 @@
 pub mod util;
 
+pub mod amount;
 pub mod econ_data;
 pub mod constraints;
+pub mod correlation;
 pub mod hash;
+pub mod histogram;
 pub mod merkle;
+pub mod monotonic;
+pub mod growth;
+pub mod moving_avg;
+pub mod order_stats;
+pub mod price_index;
+pub mod range;
+pub mod rollup;
+pub mod threshold;
+pub mod trend;
+pub mod variance;
+pub mod volatility;
+pub mod weighted;
 
 use ark_r1cs_std::fields::fp::FpVar;
 
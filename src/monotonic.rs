@@ -0,0 +1,192 @@
+use crate::{
+    amount::{Amount, AmountVar},
+    constraints::{CommittedPeriods, PublicInputs},
+    econ_data::Monotonicity,
+    range::{enforce_bit_width, enforce_cmp_small, VALUE_BITS},
+    F,
+};
+
+use ark_r1cs_std::alloc::AllocVar;
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// A ZK circuit that proves a committed series never moved the wrong way, e.g., that an indexed
+/// rent never decreased over the year, without revealing any of the prices. It can also prove
+/// that no step from one period to the next was larger than a public maximum.
+#[derive(Clone)]
+pub struct MonotonicCircuit {
+    /// The committed data for every period, and the ledger it's in, if any.
+    pub data: CommittedPeriods,
+
+    /// The direction the prices must move in. Like the number of periods, this is part of the
+    /// circuit's shape.
+    pub direction: Monotonicity,
+
+    // The analysis bounds. All of them must be below `max_value_bound()`.
+    /// The largest allowed step from one price to the next, if there is one. Whether there is one
+    /// is also part of the circuit's shape.
+    pub bounds_max_step: Option<Amount>,
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+impl PublicInputs for MonotonicCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                self.bounds_max_step.iter().map(|step| step.0).collect(),
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for MonotonicCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // allocate and open the committed data, and check every price is within the bounds
+        let (data_purchase_prices, _) = self.data.open_prices_within_bounds(
+            cs.clone(),
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        // create input for the max step
+        let bounds_max_step = self
+            .bounds_max_step
+            .map(|step| {
+                AmountVar::new_input(ns!(cs, "bounds max step"), || Ok(step)).map(|step| step.0)
+            })
+            .transpose()?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // The prices are already range checked, and so is the max step here, so the comparisons
+        // below only need to decompose VALUE_BITS bits
+        if let Some(bounds_max_step) = &bounds_max_step {
+            enforce_bit_width(cs.clone(), bounds_max_step, VALUE_BITS)?;
+        }
+
+        // check every step from one period to the next
+        for pair in data_purchase_prices.windows(2) {
+            let (smaller, larger) = match self.direction {
+                Monotonicity::NonDecreasing => (&pair[0], &pair[1]),
+                Monotonicity::NonIncreasing => (&pair[1], &pair[0]),
+            };
+
+            // CHECK: the prices move in the right direction
+            enforce_cmp_small(cs.clone(), smaller, larger, Ordering::Less, true, VALUE_BITS)?;
+
+            // CHECK: the step is at most the max step. The check above makes the step
+            // nonnegative, and both prices are range checked, so it's below 2^VALUE_BITS.
+            if let Some(bounds_max_step) = &bounds_max_step {
+                let step = larger - smaller;
+                enforce_cmp_small(
+                    cs.clone(),
+                    &step,
+                    bounds_max_step,
+                    Ordering::Less,
+                    true,
+                    VALUE_BITS,
+                )?;
+            }
+        }
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constraints::max_value_bound,
+        econ_data::max_step,
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+
+    // Sets up a monotonic circuit over the given prices, with the given direction and max step
+    fn setup(prices: &[u64], direction: Monotonicity, max_step: Option<u64>) -> MonotonicCircuit {
+        MonotonicCircuit {
+            data: gen_test_committed_prices(prices),
+            direction,
+            bounds_max_step: max_step.map(Amount::from_minor_units),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(2000),
+        }
+    }
+
+    // A monthly rent that is indexed twice a year. It stays flat in between, and the largest
+    // increase is 40.
+    const RENTS: [u64; 12] =
+        [1000, 1000, 1000, 1000, 1000, 1000, 1040, 1040, 1040, 1040, 1040, 1040];
+
+    // Flat test: Steps of 0 move in neither direction, so a flat series is both non-decreasing
+    // and non-increasing, even with a max step of 0. So is a single period, which has no steps.
+    #[test]
+    fn monotonic_flat() {
+        for direction in [Monotonicity::NonDecreasing, Monotonicity::NonIncreasing] {
+            for prices in [&[1000, 1000, 1000][..], &[1000][..]] {
+                let circuit = setup(prices, direction, Some(0));
+                assert!(is_satisfied(circuit), "rejected {prices:?} as {direction:?}");
+            }
+        }
+    }
+
+    // Direction test: The rents never decrease, and reversed they never increase. Either way, a
+    // single dip of 1 is rejected, with or without a max step.
+    #[test]
+    fn monotonic_direction() {
+        let circuit = setup(&RENTS, Monotonicity::NonDecreasing, None);
+        assert!(is_satisfied(circuit), "rejected a non-decreasing rent");
+        let circuit = setup(&RENTS, Monotonicity::NonIncreasing, None);
+        assert!(!is_satisfied(circuit), "accepted an increasing rent as non-increasing");
+
+        let mut reversed = RENTS;
+        reversed.reverse();
+        let circuit = setup(&reversed, Monotonicity::NonIncreasing, None);
+        assert!(is_satisfied(circuit), "rejected a non-increasing rent");
+
+        let mut dipped = RENTS;
+        dipped[3] = 999;
+        let dipped_rents = dipped.map(Amount::from_minor_units);
+        assert_eq!(max_step(&dipped_rents, Monotonicity::NonDecreasing), None);
+        for max_step in [None, Some(40)] {
+            let circuit = setup(&dipped, Monotonicity::NonDecreasing, max_step);
+            assert!(!is_satisfied(circuit), "accepted a dip, max step {max_step:?}");
+        }
+    }
+
+    // Max step test: The largest step of 40 is allowed by a max step of exactly 40, but not 39.
+    // A max step of 2^64 would be meaningless to the cheap comparisons, so it's rejected.
+    #[test]
+    fn monotonic_max_step() {
+        let rents = RENTS.map(Amount::from_minor_units);
+        assert_eq!(
+            max_step(&rents, Monotonicity::NonDecreasing),
+            Some(Amount::from_minor_units(40))
+        );
+
+        let circuit = setup(&RENTS, Monotonicity::NonDecreasing, Some(40));
+        assert!(is_satisfied(circuit), "rejected a step of 40 as at most 40");
+        let circuit = setup(&RENTS, Monotonicity::NonDecreasing, Some(39));
+        assert!(!is_satisfied(circuit), "accepted a step of 40 as at most 39");
+
+        let mut circuit = setup(&RENTS, Monotonicity::NonDecreasing, Some(40));
+        circuit.bounds_max_step = Some(Amount(max_value_bound() + F::from(40u32)));
+        assert!(!is_satisfied(circuit), "accepted a max step that's too large");
+    }
+}
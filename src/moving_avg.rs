@@ -0,0 +1,215 @@
+use crate::{
+    amount::{Amount, AmountVar},
+    constraints::{enforce_average, CommittedPeriods, PublicInputs},
+    econ_data::RoundingMode,
+    range::{enforce_bit_width, enforce_cmp_small, VALUE_BITS},
+    F, FV,
+};
+
+use ark_r1cs_std::alloc::AllocVar;
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// A ZK circuit that proves the rolling averages of the committed purchase prices, e.g., the
+/// 3-month rolling average of 12 monthly prices, without revealing the prices. There is one
+/// output per window, so `n` periods and a window of length `w` give `n - w + 1` outputs.
+#[derive(Clone)]
+pub struct MovingAverageCircuit {
+    /// The committed data for every period, and the ledger it's in, if any.
+    pub data: CommittedPeriods,
+
+    /// The number of consecutive periods in each window, at least 1 and at most the number of
+    /// periods. Like the number of periods, this is part of the circuit's shape.
+    pub window: usize,
+
+    /// How each window average is rounded
+    pub rounding: RoundingMode,
+
+    // The analysis outputs
+    /// The average of every window, in order. Use `econ_data::moving_averages` to compute them.
+    pub output_window_avgs: Vec<Amount>,
+
+    // The analysis bounds. The maximum must be below `max_value_bound()`.
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+impl PublicInputs for MovingAverageCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                self.output_window_avgs.iter().map(|avg| avg.0).collect(),
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for MovingAverageCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // The window has to fit in the series, and every window needs exactly one claimed average
+        let num_periods = self.data.periods.len();
+        if self.window == 0 || self.window > num_periods {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        if self.output_window_avgs.len() != num_periods - self.window + 1 {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // allocate and open the committed data, and check every price is within the bounds
+        let (data_purchase_prices, (_, bounds_purchase_price_max)) =
+            self.data.open_prices_within_bounds(
+                cs.clone(),
+                self.bounds_purchase_price_min,
+                self.bounds_purchase_price_max,
+            )?;
+
+        // create inputs for the result values
+        let output_window_avgs = self
+            .output_window_avgs
+            .iter()
+            .map(|avg| {
+                AmountVar::new_input(ns!(cs, "window avg result"), || Ok(avg)).map(|avg| avg.0)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // check every window average. Each window sum is a linear combination of the opened
+        // prices, and each average is bounded above, so avg * window can't wrap around the field.
+        for (window_prices, window_avg) in data_purchase_prices
+            .windows(self.window)
+            .zip(output_window_avgs.iter())
+        {
+            let window_sum: FV = window_prices.iter().sum();
+            enforce_average(
+                cs.clone(),
+                &window_sum,
+                self.window as u64,
+                window_avg,
+                self.rounding,
+            )?;
+            enforce_bit_width(cs.clone(), window_avg, VALUE_BITS)?;
+            enforce_cmp_small(
+                cs.clone(),
+                window_avg,
+                &bounds_purchase_price_max,
+                Ordering::Less,
+                true,
+                VALUE_BITS,
+            )?;
+        }
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        econ_data::moving_averages,
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Sets up a legitimate moving average circuit over the given prices
+    fn setup(prices: &[u64], window: usize, rounding: RoundingMode) -> MovingAverageCircuit {
+        let data = gen_test_committed_prices(prices);
+        let prices = data.purchase_prices();
+
+        MovingAverageCircuit {
+            data,
+            window,
+            rounding,
+            output_window_avgs: moving_averages(&prices, window, rounding).unwrap_or_default(),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+        }
+    }
+
+    // Overlap test: The 3-period windows of 100, 200, 300, 700, 500 sum to 600, 1200 and 1500, so
+    // the rolling averages are 200, 400 and 500. The windows share prices, so each average is
+    // checked on its own, and shifting any one of them is rejected.
+    #[test]
+    fn moving_avg_overlapping_windows() {
+        let circuit = setup(&[100, 200, 300, 700, 500], 3, RoundingMode::Exact);
+        assert_eq!(
+            circuit.output_window_avgs,
+            vec![200, 400, 500].into_iter().map(Amount::from_minor_units).collect::<Vec<_>>()
+        );
+        assert!(is_satisfied(circuit.clone()), "rejected the rolling averages");
+
+        for i in 0..circuit.output_window_avgs.len() {
+            for delta in [F::from(1u32), -F::from(1u32)] {
+                let mut bad_circuit = circuit.clone();
+                bad_circuit.output_window_avgs[i].0 += delta;
+                assert!(!is_satisfied(bad_circuit), "accepted a wrong average for window {i}");
+            }
+        }
+
+        // Swapping two averages keeps their sum, but not the windows they belong to
+        let mut swapped = circuit;
+        swapped.output_window_avgs.swap(0, 2);
+        assert!(!is_satisfied(swapped), "accepted the averages in the wrong order");
+    }
+
+    // Window size test: A window of 1 is the series itself, and a window of the whole series is
+    // its plain average, here 200.33 rounded down. Empty windows and windows longer than the
+    // series don't make a circuit, and neither does the wrong number of outputs.
+    #[test]
+    fn moving_avg_window_sizes() {
+        let circuit = setup(&[100, 200, 301], 1, RoundingMode::Exact);
+        assert_eq!(circuit.output_window_avgs, [100, 200, 301].map(Amount::from_minor_units));
+        assert!(is_satisfied(circuit), "rejected a window of 1");
+
+        let circuit = setup(&[100, 200, 301], 3, RoundingMode::Floor);
+        assert_eq!(circuit.output_window_avgs, vec![Amount::from_minor_units(200)]);
+        assert!(is_satisfied(circuit), "rejected a window of the whole series");
+
+        for window in [0, 4] {
+            let mut bad_circuit = setup(&[100, 200, 301], 3, RoundingMode::Floor);
+            bad_circuit.window = window;
+            let cs = ConstraintSystem::new_ref();
+            assert!(bad_circuit.generate_constraints(cs).is_err(), "accepted a window of {window}");
+        }
+
+        let mut bad_circuit = setup(&[100, 200, 300, 700, 500], 3, RoundingMode::Floor);
+        bad_circuit.output_window_avgs.pop();
+        let cs = ConstraintSystem::new_ref();
+        assert!(bad_circuit.generate_constraints(cs).is_err());
+    }
+
+    // Rounding test: The windows of 100, 101, 103 average 100.5 and 102. The half rounds up when
+    // rounding to the nearest, and down with the floor, and there's no exact average.
+    #[test]
+    fn moving_avg_half() {
+        let prices = [100, 101, 103];
+        let nearest = setup(&prices, 2, RoundingMode::Nearest);
+        assert_eq!(nearest.output_window_avgs, [101, 102].map(Amount::from_minor_units));
+        assert!(is_satisfied(nearest), "rejected rounding a half up");
+
+        let floor = setup(&prices, 2, RoundingMode::Floor);
+        assert_eq!(floor.output_window_avgs, [100, 102].map(Amount::from_minor_units));
+        assert!(is_satisfied(floor.clone()), "rejected rounding a half down");
+
+        let mut inexact = floor;
+        inexact.rounding = RoundingMode::Exact;
+        assert!(!is_satisfied(inexact), "accepted an exact average of a half");
+    }
+}
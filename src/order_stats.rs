@@ -0,0 +1,249 @@
+use crate::{
+    amount::{Amount, AmountVar},
+    constraints::{CommittedPeriods, PublicInputs},
+    econ_data::OrderStatistic,
+    F, FV,
+};
+
+use ark_ff::PrimeField;
+use ark_r1cs_std::{alloc::AllocVar, boolean::Boolean, eq::EqGadget, fields::FieldVar, R1CSVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// Witnesses a sorted copy of the given values and proves that it really is one. Returns the
+/// sorted copy, smallest value first.
+///
+/// Concretely, we witness a permutation matrix `P` of bits, where `P[i][j]` is set iff the i-th
+/// value ends up in position j. Every row and every column has exactly one bit set, and position j
+/// of the sorted copy is `Σ_i P[i][j]·values[i]`. This costs `n²` constraints, but doesn't need a
+/// random challenge, which we couldn't get without hashing every value in the circuit. Finally, we
+/// check that consecutive positions are in order with `enforce_cmp`.
+pub fn sorted_copy(cs: ConstraintSystemRef<F>, values: &[FV]) -> Result<Vec<FV>, SynthesisError> {
+    let n = values.len();
+
+    // Work out where every value goes. When we're only generating the proving key, the values
+    // aren't known, and neither is the permutation.
+    let positions: Result<Vec<usize>, SynthesisError> = values
+        .iter()
+        .map(|value| value.value())
+        .collect::<Result<Vec<F>, _>>()
+        .map(|native_values| {
+            let mut order: Vec<usize> = (0..n).collect();
+            order.sort_by_key(|&i| native_values[i].into_bigint());
+
+            // order[j] is the index of the value at sorted position j. Invert it.
+            let mut positions = vec![0; n];
+            for (j, &i) in order.iter().enumerate() {
+                positions[i] = j;
+            }
+            positions
+        });
+
+    // Witness the permutation matrix
+    let permutation = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| {
+                    Boolean::new_witness(ns!(cs, "permutation bit"), || {
+                        positions.as_ref().map(|positions| positions[i] == j).map_err(|e| *e)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Every value goes to exactly one position, and every position gets exactly one value
+    for i in 0..n {
+        let row_sum = permutation[i]
+            .iter()
+            .fold(FV::zero(), |acc, bit| acc + FV::from(bit.clone()));
+        row_sum.enforce_equal(&FV::one())?;
+
+        let col_sum = permutation
+            .iter()
+            .fold(FV::zero(), |acc, row| acc + FV::from(row[i].clone()));
+        col_sum.enforce_equal(&FV::one())?;
+    }
+
+    // Move the values into their positions
+    let sorted = (0..n)
+        .map(|j| {
+            let moved_values = values
+                .iter()
+                .zip(permutation.iter())
+                .map(|(value, row)| row[j].select(value, &FV::zero()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(moved_values.iter().sum::<FV>())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // And check they're in order
+    for pair in sorted.windows(2) {
+        pair[0].enforce_cmp(&pair[1], Ordering::Less, true)?;
+    }
+
+    Ok(sorted)
+}
+
+/// A ZK circuit that proves order statistics of the committed purchase prices, e.g., "the median
+/// monthly price is M" or "the 3rd smallest monthly price is v", without revealing the prices.
+#[derive(Clone)]
+pub struct OrderStatCircuit {
+    /// The committed data for every period, and the ledger it's in, if any.
+    pub data: CommittedPeriods,
+
+    /// Which order statistics are proven, i.e., which positions of the sorted prices are read
+    pub statistics: Vec<OrderStatistic>,
+
+    // The analysis outputs
+    /// The value of each of the statistics above, in the same order. Use
+    /// `econ_data::order_statistic` to compute them.
+    pub output_order_stats: Vec<Amount>,
+}
+
+impl PublicInputs for OrderStatCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs()?,
+                self.output_order_stats.iter().map(|stat| stat.0).collect(),
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for OrderStatCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // Every statistic needs exactly one claimed value
+        if self.statistics.len() != self.output_order_stats.len() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // allocate and open the committed data
+        let data_purchase_prices = self.data.open_prices(cs.clone())?;
+
+        // create inputs for the result values
+        let output_order_stats = self
+            .output_order_stats
+            .iter()
+            .map(|stat| {
+                AmountVar::new_input(ns!(cs, "order stat result"), || Ok(stat)).map(|stat| stat.0)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // Sort the prices
+        let sorted_prices = sorted_copy(cs.clone(), &data_purchase_prices)?;
+
+        // check the statistics. Each one is the sum of the sorted prices at its positions.
+        for (statistic, output) in self.statistics.iter().zip(output_order_stats.iter()) {
+            let positions = statistic
+                .sorted_positions(sorted_prices.len())
+                .ok_or(SynthesisError::Unsatisfiable)?;
+            let computed_stat: FV = positions.iter().map(|&pos| &sorted_prices[pos]).sum();
+            computed_stat.enforce_equal(output)?;
+        }
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        econ_data::order_statistic,
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Sets up a legitimate order statistic circuit over the given prices
+    fn setup(prices: &[u64], statistics: &[OrderStatistic]) -> OrderStatCircuit {
+        let data = gen_test_committed_prices(prices);
+        let prices = data.purchase_prices();
+
+        let output_order_stats = statistics
+            .iter()
+            .map(|&statistic| order_statistic(&prices, statistic).unwrap())
+            .collect();
+
+        OrderStatCircuit {
+            data,
+            statistics: statistics.to_vec(),
+            output_order_stats,
+        }
+    }
+
+    // Ties test: 500, 300, 900, 100, 300 sorts to 100, 300, 300, 500, 900, so the median is one of
+    // two equal prices. The minimum is 100, and the 4th smallest is 500. With the 900 dropped, the
+    // median is halfway between the tied 300s, and with the 100 dropped it's halfway between 300
+    // and 500.
+    #[test]
+    fn order_stat_ties() {
+        let statistics = [
+            OrderStatistic::DoubleMedian,
+            OrderStatistic::Kth(0),
+            OrderStatistic::Kth(3),
+        ];
+        let circuit = setup(&[500, 300, 900, 100, 300], &statistics);
+        assert_eq!(
+            circuit.output_order_stats,
+            vec![600, 100, 500].into_iter().map(Amount::from_minor_units).collect::<Vec<_>>()
+        );
+        assert!(is_satisfied(circuit), "rejected order statistics with a tie");
+
+        let circuit = setup(&[500, 300, 100, 300], &[OrderStatistic::DoubleMedian]);
+        assert_eq!(circuit.output_order_stats, vec![Amount::from_minor_units(600)]);
+        assert!(is_satisfied(circuit), "rejected an even length median between tied prices");
+
+        let circuit = setup(&[500, 300, 900, 300], &[OrderStatistic::DoubleMedian]);
+        assert_eq!(circuit.output_order_stats, vec![Amount::from_minor_units(800)]);
+        assert!(is_satisfied(circuit), "rejected an even length median");
+    }
+
+    // Position test: Claim a statistic that's off by one, or one that is a committed price but in
+    // the wrong position. The 900 is the maximum, and 200 is the doubled minimum.
+    #[test]
+    fn order_stat_wrong_position() {
+        let statistics = [OrderStatistic::DoubleMedian, OrderStatistic::Kth(3)];
+        let circuit = setup(&[500, 300, 900, 100, 300], &statistics);
+
+        for bad_stats in [[599u64, 500], [600, 501], [600, 900], [200, 500]] {
+            let mut bad_circuit = circuit.clone();
+            bad_circuit.output_order_stats =
+                bad_stats.iter().map(|&s| Amount::from_minor_units(s)).collect();
+            assert!(!is_satisfied(bad_circuit), "accepted {bad_stats:?} as order statistics");
+        }
+    }
+
+    // An empty series has no order statistics, and neither does one that's too short. Neither is
+    // allowed to reach a position that isn't there.
+    #[test]
+    fn order_stat_too_few_values() {
+        assert_eq!(OrderStatistic::DoubleMedian.sorted_positions(0), None);
+        assert_eq!(OrderStatistic::Kth(0).sorted_positions(0), None);
+        assert_eq!(OrderStatistic::Kth(3).sorted_positions(3), None);
+        assert_eq!(order_statistic(&[], OrderStatistic::DoubleMedian), None);
+
+        let mut circuit = setup(&[500, 300, 900], &[OrderStatistic::DoubleMedian]);
+        circuit.data.periods.clear();
+        assert!(circuit.generate_constraints(ConstraintSystem::new_ref()).is_err());
+
+        let mut circuit = setup(&[500, 300, 900], &[OrderStatistic::Kth(0)]);
+        circuit.statistics = vec![OrderStatistic::Kth(3)];
+        assert!(circuit.generate_constraints(ConstraintSystem::new_ref()).is_err());
+    }
+}
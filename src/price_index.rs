@@ -0,0 +1,374 @@
+use crate::{
+    amount::{Amount, AmountVar},
+    constraints::{
+        data_public_inputs, enforce_price_bounds, open_periods, public_coms, Period, PublicInputs,
+    },
+    econ_data::{BasketGood, IndexFormula},
+    hash::{LeafHash, TwoToOneHash},
+    merkle::MerkleRoot,
+    range::{enforce_bit_width, enforce_cmp_small, VALUE_BITS},
+    F, FV,
+};
+
+use ark_crypto_primitives::crh::{CRHScheme, TwoToOneCRHScheme};
+use ark_ff::Field;
+use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::FieldVar, R1CSVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// The most decimal digits a price index can have. This keeps the scaled numerator far below the
+/// field size.
+pub const MAX_INDEX_DECIMALS: u32 = 18;
+
+/// A ZK circuit that proves the price index of a basket of goods in every period, relative to a
+/// public base-period basket, without revealing any prices. Each period commits to one `Data` per
+/// good. The index is 100 in the base period, and is given in fixed point with `index_decimals`
+/// digits. See `econ_data::price_index`.
+///
+/// The index of a period is `100 · num / den`, where `num` and `den` depend on the formula. The
+/// circuit accepts a claimed index I if it's within the public precision ε of the true index,
+/// i.e., `(I − ε)·den <= scale·num <= (I + ε)·den`, where `scale = 100 · 10^index_decimals`.
+#[derive(Clone)]
+pub struct PriceIndexCircuit {
+    // These are constants that will be embedded into the circuit. They describe how the hash
+    // function works. Don't worry about this.
+    pub leaf_crh_params: <LeafHash as CRHScheme>::Parameters,
+    pub two_to_one_crh_params: <TwoToOneHash as TwoToOneCRHScheme>::Parameters,
+
+    /// The data for every good in every period being analyzed, so `periods[t][g]` is good g in
+    /// period t. Every period has one entry per good in `basket`. The commitments are opened in
+    /// this order, period by period. See `AnalysisCircuit::periods`.
+    pub periods: Vec<Vec<Period>>,
+
+    /// The root of the public ledger. See `AnalysisCircuit::ledger_root`.
+    pub ledger_root: Option<MerkleRoot>,
+
+    /// How the goods are weighed. The two formulas multiply different variables, so each has its
+    /// own proving key.
+    pub formula: IndexFormula,
+    /// The number of decimal digits of the index, at most `MAX_INDEX_DECIMALS`. The scale
+    /// `100 · 10^index_decimals` is a constant in the constraints.
+    pub index_decimals: u32,
+
+    /// The public base-period basket, with one entry per good. Its prices and weights must be below
+    /// `max_value_bound()`.
+    pub basket: Vec<BasketGood>,
+
+    // The analysis outputs
+    /// The index of every period, in order
+    pub output_indices: Vec<F>,
+
+    // The analysis bounds. The maximums must be below `max_value_bound()`.
+    /// How far each claimed index may be from the true one, in the same fixed point
+    pub bounds_index_precision: F,
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+    pub bounds_quantity_max: F,
+}
+
+impl PublicInputs for PriceIndexCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        let periods: Vec<Period> = self.periods.concat();
+        let coms = public_coms(self.ledger_root.as_ref(), &periods);
+        let basket: Vec<F> = self
+            .basket
+            .iter()
+            .flat_map(|good| [good.base_price.0, good.weight])
+            .collect();
+        Some(
+            [
+                data_public_inputs(self.ledger_root.as_ref(), &coms)?,
+                self.output_indices.clone(),
+                vec![self.bounds_index_precision],
+                basket,
+                vec![
+                    self.bounds_purchase_price_min.0,
+                    self.bounds_purchase_price_max.0,
+                    self.bounds_quantity_max,
+                ],
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for PriceIndexCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // Every period needs every good, and exactly one claimed index
+        let num_goods = self.basket.len();
+        if self.periods.iter().any(|goods| goods.len() != num_goods)
+            || self.output_indices.len() != self.periods.len()
+            || self.index_decimals > MAX_INDEX_DECIMALS
+        {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // Allocate and open the data for every good in every period, exactly like
+        // AnalysisCircuit does
+        let data_vars = open_periods(
+            cs.clone(),
+            &self.leaf_crh_params,
+            &self.two_to_one_crh_params,
+            &self.periods.concat(),
+            self.ledger_root.as_ref(),
+        )?;
+
+        // create inputs for the result values
+        let output_indices = self
+            .output_indices
+            .iter()
+            .map(|index| FV::new_input(ns!(cs, "index result"), || Ok(index)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // create inputs for the precision and the base basket
+        let bounds_index_precision = FV::new_input(ns!(cs, "bounds index precision"), || {
+            Ok(&self.bounds_index_precision)
+        })?;
+        let basket = self
+            .basket
+            .iter()
+            .map(|good| {
+                let base_price =
+                    AmountVar::new_input(ns!(cs, "basket price"), || Ok(&good.base_price))?.0;
+                let weight = FV::new_input(ns!(cs, "basket weight"), || Ok(&good.weight))?;
+                Ok((base_price, weight))
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        // create inputs for the threshold values, and check every price is within the bounds
+        let prices: Vec<FV> = data_vars.iter().map(|data_var| data_var.amount.0.clone()).collect();
+        let _ = enforce_price_bounds(
+            cs.clone(),
+            &prices,
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+        let bounds_quantity_max =
+            FV::new_input(ns!(cs, "bounds quantity"), || Ok(&self.bounds_quantity_max))?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // bounds constraints. With every price, weight, quantity, index and the precision below
+        // 2^64, none of the products below can wrap around the field.
+        enforce_bit_width(cs.clone(), &bounds_quantity_max, VALUE_BITS)?;
+        enforce_bit_width(cs.clone(), &bounds_index_precision, VALUE_BITS)?;
+        for (base_price, weight) in &basket {
+            enforce_bit_width(cs.clone(), base_price, VALUE_BITS)?;
+            enforce_bit_width(cs.clone(), weight, VALUE_BITS)?;
+        }
+        for index in &output_indices {
+            enforce_bit_width(cs.clone(), index, VALUE_BITS)?;
+        }
+        for data_var in &data_vars {
+            enforce_bit_width(cs.clone(), &data_var.quantity, VALUE_BITS)?;
+            enforce_cmp_small(
+                cs.clone(),
+                &data_var.quantity,
+                &bounds_quantity_max,
+                Ordering::Less,
+                true,
+                VALUE_BITS,
+            )?;
+        }
+
+        // check the index of every period
+        let scale = F::from(100 * 10u128.pow(self.index_decimals));
+        for (period_data_vars, index) in data_vars.chunks(num_goods).zip(output_indices.iter()) {
+            // compute the cost of the basket now and in the base period. Each good is weighed by
+            // its basket weight or by the quantity bought now, depending on the formula.
+            let mut numerator = FV::zero();
+            let mut denominator = FV::zero();
+            for (data_var, (base_price, weight)) in period_data_vars.iter().zip(basket.iter()) {
+                let weight = match self.formula {
+                    IndexFormula::Laspeyres => weight,
+                    IndexFormula::Paasche => &data_var.quantity,
+                };
+                numerator += weight * &data_var.amount.0;
+                denominator += weight * base_price;
+            }
+
+            // The index is undefined if the base basket costs nothing. The denominator is a sum of
+            // products of small values, so it's never negative, and being nonzero is enough. We
+            // witness its inverse, which only exists if it's nonzero.
+            let denominator_inv = FV::new_witness(ns!(cs, "index denominator inverse"), || {
+                Ok(denominator.value()?.inverse().unwrap_or_default())
+            })?;
+            (&denominator * &denominator_inv).enforce_equal(&FV::one())?;
+
+            // CHECK: (I − ε)·den <= scale·num <= (I + ε)·den. We move ε·den to the other side of
+            // the lower bound, so that nothing here is negative.
+            let scaled_numerator = numerator * scale;
+            let index_times_den = index * &denominator;
+            let precision_times_den = &bounds_index_precision * &denominator;
+            (&scaled_numerator + &precision_times_den).enforce_cmp(
+                &index_times_den,
+                Ordering::Greater,
+                true,
+            )?;
+            scaled_numerator.enforce_cmp(
+                &(index_times_den + precision_times_den),
+                Ordering::Less,
+                true,
+            )?;
+        }
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constraints::max_value_bound,
+        econ_data::{price_index, Data},
+        util::{gen_test_periods_from_data, test_harness::is_satisfied},
+    };
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // A basket of 10 loaves of bread at 200 and 20 bottles of milk at 100, so it costs 4000 in the
+    // base period
+    fn basket() -> Vec<BasketGood> {
+        vec![
+            BasketGood { base_price: Amount::from_minor_units(200), weight: F::from(10u32) },
+            BasketGood { base_price: Amount::from_minor_units(100), weight: F::from(20u32) },
+        ]
+    }
+
+    // Sets up a legitimate price index circuit over the given (price, quantity) pairs, with one
+    // pair per good in every period, and a precision of 1
+    fn setup(
+        periods: &[[(u64, u64); 2]],
+        formula: IndexFormula,
+        index_decimals: u32,
+    ) -> PriceIndexCircuit {
+        let mut rng = ark_std::test_rng();
+        let leaf_crh_params = <LeafHash as CRHScheme>::setup(&mut rng).unwrap();
+        let two_to_one_crh_params = <TwoToOneHash as TwoToOneCRHScheme>::setup(&mut rng).unwrap();
+
+        let data: Vec<Vec<Data>> = periods
+            .iter()
+            .map(|goods| {
+                goods
+                    .iter()
+                    .map(|&(price, quantity)| Data {
+                        purchase_price: Amount::from_minor_units(price),
+                        quantity: F::from(quantity),
+                    })
+                    .collect()
+            })
+            .collect();
+        let output_indices = data
+            .iter()
+            .map(|goods| price_index(&basket(), goods, formula, index_decimals).unwrap())
+            .collect();
+        let periods = data
+            .iter()
+            .map(|goods| gen_test_periods_from_data(&leaf_crh_params, goods, &mut rng))
+            .collect();
+
+        PriceIndexCircuit {
+            leaf_crh_params,
+            two_to_one_crh_params,
+            periods,
+            ledger_root: None,
+            formula,
+            index_decimals,
+            basket: basket(),
+            output_indices,
+            bounds_index_precision: F::from(1u32),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+            bounds_quantity_max: F::from(1000),
+        }
+    }
+
+    // In the first period, 5 loaves of bread cost 210 and 30 bottles of milk cost 110. In the
+    // second, prices are back to the base period's.
+    const PERIODS: [[(u64, u64); 2]; 2] = [[(210, 5), (110, 30)], [(200, 8), (100, 25)]];
+
+    // Formula test: The Laspeyres index of the first period is 100 · 4300 / 4000 = 107.5. The
+    // Paasche index weighs by what was bought instead, so it's 100 · 4350 / 4000 = 108.75. Both
+    // are 100 in the second period, and one formula's index isn't accepted for the other.
+    #[test]
+    fn price_index_formulas() {
+        let laspeyres = setup(&PERIODS, IndexFormula::Laspeyres, 1);
+        assert_eq!(laspeyres.output_indices, vec![F::from(1075u32), F::from(1000u32)]);
+        assert!(is_satisfied(laspeyres.clone()), "rejected the Laspeyres index");
+
+        let paasche = setup(&PERIODS, IndexFormula::Paasche, 1);
+        assert_eq!(paasche.output_indices, vec![F::from(1087u32), F::from(1000u32)]);
+        assert!(is_satisfied(paasche.clone()), "rejected the Paasche index");
+
+        let mut swapped = laspeyres;
+        swapped.output_indices = paasche.output_indices;
+        assert!(!is_satisfied(swapped), "accepted the Paasche index as the Laspeyres one");
+    }
+
+    // Precision test: 108.75 lies between 108.7 and 108.8, and both are within 0.1 of it, but
+    // nothing further out is. With no slack at all, only an exact index like 107.5 passes, and
+    // 108.75 has no exact index with one decimal.
+    #[test]
+    fn price_index_precision() {
+        for (index, accepted) in [(1086u32, false), (1087, true), (1088, true), (1089, false)] {
+            let mut circuit = setup(&PERIODS, IndexFormula::Paasche, 1);
+            circuit.output_indices[0] = F::from(index);
+            assert_eq!(is_satisfied(circuit), accepted, "wrong result for index {index}");
+        }
+
+        for (formula, index, accepted) in [
+            (IndexFormula::Laspeyres, 1075u32, true),
+            (IndexFormula::Laspeyres, 1076, false),
+            (IndexFormula::Paasche, 1087, false),
+            (IndexFormula::Paasche, 1088, false),
+        ] {
+            let mut circuit = setup(&PERIODS, formula, 1);
+            circuit.bounds_index_precision = F::from(0u32);
+            circuit.output_indices[0] = F::from(index);
+            assert_eq!(is_satisfied(circuit), accepted, "wrong result for exact index {index}");
+        }
+    }
+
+    // Empty basket test: If every basket weight is 0, the base basket costs nothing, and so does
+    // the current one. Then any index would satisfy the precision band, so the circuit has to
+    // reject the basket itself.
+    #[test]
+    fn price_index_empty_basket() {
+        let mut circuit = setup(&PERIODS, IndexFormula::Laspeyres, 1);
+        for good in circuit.basket.iter_mut() {
+            good.weight = F::from(0u32);
+        }
+        assert!(!is_satisfied(circuit), "accepted an index of a basket that costs nothing");
+    }
+
+    // Range test: A weight or precision of 2^64 could let the products wrap around the field, and
+    // an index of −1 is p − 1. The wrong number of indices doesn't make a circuit at all.
+    #[test]
+    fn price_index_out_of_range() {
+        let mut circuit = setup(&PERIODS, IndexFormula::Laspeyres, 1);
+        circuit.basket[0].weight = max_value_bound();
+        assert!(!is_satisfied(circuit), "accepted a weight that's too large");
+
+        let mut circuit = setup(&PERIODS, IndexFormula::Laspeyres, 1);
+        circuit.bounds_index_precision = max_value_bound();
+        assert!(!is_satisfied(circuit), "accepted a precision that's too large");
+
+        let mut circuit = setup(&PERIODS, IndexFormula::Laspeyres, 1);
+        circuit.output_indices[0] = -F::from(1u32);
+        assert!(!is_satisfied(circuit), "accepted a negative index");
+
+        let mut circuit = setup(&PERIODS, IndexFormula::Laspeyres, 1);
+        circuit.output_indices.pop();
+        assert!(circuit.generate_constraints(ConstraintSystem::new_ref()).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{F, FV};
+
+use ark_ff::{BigInteger, Field, PrimeField};
+use ark_r1cs_std::{alloc::AllocVar, boolean::Boolean, eq::EqGadget, fields::FieldVar, R1CSVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// The bit width of prices, quantities and bounds. Values of this width are exactly the ones below
+/// `max_value_bound()`.
+pub const VALUE_BITS: usize = 64;
+
+/// The widest range check we allow. Comparisons decompose a value one bit wider than their
+/// operands, and `2^(bits + 1)` has to stay below the field size so differences can't wrap around.
+pub const MAX_RANGE_BITS: usize = 250;
+
+/// Enforces that `0 <= x < 2^bits` by witnessing the `bits` low bits of `x` and checking that they
+/// add up to `x`. This costs `bits + 1` constraints, where `FpVar::enforce_cmp` decomposes full
+/// field elements every time. Returns the bits, least significant first.
+pub fn enforce_bit_width(
+    cs: ConstraintSystemRef<F>,
+    x: &FV,
+    bits: usize,
+) -> Result<Vec<Boolean<F>>, SynthesisError> {
+    if bits > MAX_RANGE_BITS {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+
+    // The bits of x's value. If x doesn't fit, these are only its low bits, and the sum check
+    // below fails.
+    let x_bits = x.value().map(|x| x.into_bigint().to_bits_le());
+    let bit_vars = (0..bits)
+        .map(|i| {
+            Boolean::new_witness(ns!(cs, "range bit"), || {
+                x_bits.as_ref().map(|x_bits| x_bits[i]).map_err(|e| *e)
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // CHECK: Σ b_i·2^i == x. The sum is a linear combination, so this is a single constraint.
+    let mut power = F::from(1u32);
+    let mut sum = FV::zero();
+    for bit in &bit_vars {
+        sum += FV::from(bit.clone()) * power;
+        power = power + power;
+    }
+    sum.enforce_equal(x)?;
+
+    Ok(bit_vars)
+}
+
+/// Enforces `a ordering b`, or `a == b` if `should_also_check_equality` is set, like
+/// `FpVar::enforce_cmp`. The caller must have already checked that both `a` and `b` are below
+/// `2^bits`, e.g., with `enforce_bit_width`. Then the difference of the two is below `2^bits` iff
+/// the comparison holds, which costs `bits + 1` constraints to check.
+pub fn enforce_cmp_small(
+    cs: ConstraintSystemRef<F>,
+    a: &FV,
+    b: &FV,
+    ordering: Ordering,
+    should_also_check_equality: bool,
+    bits: usize,
+) -> Result<(), SynthesisError> {
+    let diff = small_diff(a, b, ordering, should_also_check_equality)?;
+    enforce_bit_width(cs, &diff, bits)?;
+    Ok(())
+}
+
+/// Returns whether `a ordering b`, or `a == b` if `should_also_check_equality` is set, like
+/// `FpVar::is_cmp`. As with `enforce_cmp_small`, both `a` and `b` must already be known to be
+/// below `2^bits`. The difference is shifted up by `2^bits` so it's never negative, and its top
+/// bit is the result.
+pub fn is_cmp_small(
+    cs: ConstraintSystemRef<F>,
+    a: &FV,
+    b: &FV,
+    ordering: Ordering,
+    should_also_check_equality: bool,
+    bits: usize,
+) -> Result<Boolean<F>, SynthesisError> {
+    if bits >= MAX_RANGE_BITS {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let diff = small_diff(a, b, ordering, should_also_check_equality)?;
+    let shifted_diff = diff + F::from(2u32).pow([bits as u64]);
+    let diff_bits = enforce_bit_width(cs, &shifted_diff, bits + 1)?;
+    Ok(diff_bits[bits].clone())
+}
+
+// Returns the difference that is in [0, 2^bits) iff the comparison holds. `a < b` holds iff
+// `b - a - 1 >= 0`, and `a <= b` iff `b - a >= 0`.
+fn small_diff(
+    a: &FV,
+    b: &FV,
+    ordering: Ordering,
+    should_also_check_equality: bool,
+) -> Result<FV, SynthesisError> {
+    let (smaller, larger) = match ordering {
+        Ordering::Less => (a, b),
+        Ordering::Greater => (b, a),
+        Ordering::Equal => return Err(SynthesisError::Unsatisfiable),
+    };
+    let diff = larger - smaller;
+    if should_also_check_equality {
+        Ok(diff)
+    } else {
+        Ok(diff - F::from(1u32))
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Checks whether `a ordering b` is accepted by both comparisons, after range checking a and b
+    fn cmp_holds(a: u64, b: u64, ordering: Ordering, or_equal: bool) -> bool {
+        let cs = ConstraintSystem::new_ref();
+        let a = FV::new_witness(ns!(cs, "a"), || Ok(F::from(a))).unwrap();
+        let b = FV::new_witness(ns!(cs, "b"), || Ok(F::from(b))).unwrap();
+        enforce_bit_width(cs.clone(), &a, 8).unwrap();
+        enforce_bit_width(cs.clone(), &b, 8).unwrap();
+
+        let is_cmp = is_cmp_small(cs.clone(), &a, &b, ordering, or_equal, 8).unwrap();
+        enforce_cmp_small(cs.clone(), &a, &b, ordering, or_equal, 8).unwrap();
+
+        // The boolean must agree with whether the enforced comparison is satisfied
+        let holds = cs.is_satisfied().unwrap();
+        assert_eq!(is_cmp.value().unwrap(), holds);
+        holds
+    }
+
+    // Range test: Values are accepted exactly when they fit in the declared width, including ones
+    // that are only too large modulo the field
+    #[test]
+    fn bit_width() {
+        for (x, bits, fits) in [
+            (F::from(0u32), 8, true),
+            (F::from(255u32), 8, true),
+            (F::from(256u32), 8, false),
+            (F::from(u64::MAX), VALUE_BITS, true),
+            (F::from(1u128 << 64), VALUE_BITS, false),
+            (-F::from(1u32), VALUE_BITS, false),
+        ] {
+            let cs = ConstraintSystem::new_ref();
+            let x_var = FV::new_witness(ns!(cs, "x"), || Ok(x)).unwrap();
+            enforce_bit_width(cs.clone(), &x_var, bits).unwrap();
+            assert_eq!(cs.is_satisfied().unwrap(), fits, "{x} in {bits} bits");
+            assert_eq!(cs.num_constraints(), bits + 1);
+        }
+    }
+
+    // Comparison test: Check every ordering on small values, including the edges of the width
+    #[test]
+    fn small_comparisons() {
+        for (a, b) in [(3, 5), (5, 3), (4, 4), (0, 255), (255, 0)] {
+            assert_eq!(cmp_holds(a, b, Ordering::Less, false), a < b);
+            assert_eq!(cmp_holds(a, b, Ordering::Less, true), a <= b);
+            assert_eq!(cmp_holds(a, b, Ordering::Greater, false), a > b);
+            assert_eq!(cmp_holds(a, b, Ordering::Greater, true), a >= b);
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    amount::Amount,
+    constraints::{
+        alloc_presence, data_public_inputs, open_periods, public_coms, AnalysisCircuit, Period,
+        PublicInputs,
+    },
+    range::{enforce_bit_width, VALUE_BITS},
+    F, FV,
+};
+
+use ark_ff::ToConstraintField;
+use ark_r1cs_std::{eq::EqGadget, fields::FieldVar};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+
+/// One slot for a committed transaction in a `RollupCircuit` period
+#[derive(Clone)]
+pub struct TransactionSlot {
+    /// The committed transaction, whose purchase price is the amount of that one purchase. In a
+    /// padding slot this is a commitment to an amount of 0 with fresh randomness. It's opened like
+    /// any other, so in ledger mode it has to be a leaf of the ledger too.
+    pub transaction: Period,
+    /// Whether the slot is unused. This is only bookkeeping for `period_totals`. The circuit adds
+    /// up every slot, so a slot marked as padding still counts the amount it commits to.
+    pub is_padding: bool,
+}
+
+/// A ZK circuit for a ledger that stores individual purchases rather than per-period totals. Every
+/// period has the same number of slots, each holding either one committed transaction or padding.
+/// The circuit adds up the purchase prices of the transactions in each period, and then runs the
+/// analysis of `AnalysisCircuit` on those totals, exactly as if they were committed per-period
+/// prices.
+///
+/// Padding commits to an amount of 0, so the verifier doesn't learn how many transactions a period
+/// has, and since every slot is opened and added up, calling a transaction padding doesn't drop
+/// it. An absent period must consist of padding only. What the circuit can't check is that the
+/// slots hold every transaction there is: outside ledger mode the verifier has to recognize the
+/// slot commitments, and in ledger mode the prover picks which leaves fill the slots, just like
+/// the periods of `AnalysisCircuit`.
+#[derive(Clone)]
+pub struct RollupCircuit {
+    /// The transaction slots of every period. Every period has the same number of slots, which is
+    /// the most transactions a period can have. Like the number of periods, this is part of the
+    /// circuit's shape.
+    pub slots: Vec<Vec<TransactionSlot>>,
+
+    /// The analysis to run on the per-period totals. Its `periods` must be empty, since the periods
+    /// are rolled up from `slots` instead. Everything else works as usual: in ledger mode the
+    /// transactions are proven to be leaves of the ledger, and the presence mask, partition,
+    /// outputs and bounds all apply to the per-period totals.
+    pub analysis: AnalysisCircuit,
+}
+
+impl RollupCircuit {
+    /// Returns the total purchase price of every period, skipping padding. The analysis outputs
+    /// are computed from these, e.g., with `econ_data::subtotals`.
+    pub fn period_totals(&self) -> Vec<Amount> {
+        self.slots
+            .iter()
+            .map(|slots| {
+                let prices = slots.iter().filter(|slot| !slot.is_padding);
+                Amount(prices.map(|slot| slot.transaction.data_purchase_price.0).sum())
+            })
+            .collect()
+    }
+
+    // Returns the transaction in every slot, one period after the other
+    fn transactions(&self) -> Vec<Period> {
+        self.slots.iter().flatten().map(|slot| slot.transaction.clone()).collect()
+    }
+}
+
+// Outside of ledger mode every slot's commitment is public, including the padding ones. Those
+// commit to 0 with fresh randomness, so they look like any other and the number of transactions
+// stays private.
+impl PublicInputs for RollupCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        let ledger_root = self.analysis.ledger_root.as_ref();
+        let coms = public_coms(ledger_root, &self.transactions());
+        Some(
+            [
+                data_public_inputs(ledger_root, &coms)?,
+                self.analysis.statement().outputs.to_field_elements()?,
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for RollupCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        let num_periods = self.slots.len();
+        let max_transactions = self.slots.first().map_or(0, Vec::len);
+        if !self.analysis.periods.is_empty()
+            || max_transactions == 0
+            || self.slots.iter().any(|slots| slots.len() != max_transactions)
+        {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // Allocate and open every slot, padding included. This is exactly how AnalysisCircuit
+        // opens its periods.
+        let transaction_vars = open_periods(
+            cs.clone(),
+            &self.analysis.leaf_crh_params,
+            &self.analysis.two_to_one_crh_params,
+            &self.transactions(),
+            self.analysis.ledger_root.as_ref(),
+        )?;
+
+        // Allocate the presence mask of the periods. If it's public, these are inputs right after
+        // the commitments, just like in AnalysisCircuit.
+        let present = alloc_presence(cs.clone(), self.analysis.presence.as_ref(), num_periods)?;
+
+        // CHECK: Rollup. Each period's total is the sum of every slot, and padding commits to 0.
+        // Every amount is range checked, so the sum can't wrap around the field, and the analysis
+        // range checks the totals themselves.
+        let mut period_totals = Vec::with_capacity(num_periods);
+        for (period, present) in transaction_vars.chunks(max_transactions).zip(&present) {
+            let mut period_total = FV::zero();
+            for transaction in period {
+                let price = &transaction.amount.0;
+                enforce_bit_width(cs.clone(), price, VALUE_BITS)?;
+                period_total += price;
+            }
+
+            // CHECK: an absent period is all padding. The amounts aren't negative, so a total of
+            // 0 means every one of them is 0. Without a mask this costs nothing.
+            present.select(&FV::zero(), &period_total)?.enforce_equal(&FV::zero())?;
+
+            period_totals.push(period_total);
+        }
+
+        // Run the usual analysis on the totals
+        self.analysis.enforce_statistics(cs, &period_totals, &present)
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constraints::PresenceMask,
+        econ_data::{subtotals, RoundingMode},
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+
+    // The most transactions a period can have in these tests
+    const MAX_TRANSACTIONS: usize = 4;
+
+    // Sets up a rollup circuit with one period per list of transaction prices. The rest of each
+    // period's slots are padding, which commits to 0. The outputs are a single subtotal, the
+    // total, the minimum and the maximum of the per-period totals.
+    fn setup(transactions: &[&[u64]]) -> RollupCircuit {
+        let padded_prices: Vec<u64> = transactions
+            .iter()
+            .flat_map(|prices| {
+                let mut padded = prices.to_vec();
+                padded.resize(MAX_TRANSACTIONS, 0);
+                padded
+            })
+            .collect();
+        let data = gen_test_committed_prices(&padded_prices);
+
+        let slots: Vec<Vec<TransactionSlot>> = data
+            .periods
+            .chunks(MAX_TRANSACTIONS)
+            .zip(transactions)
+            .map(|(period, prices)| {
+                period
+                    .iter()
+                    .enumerate()
+                    .map(|(i, transaction)| TransactionSlot {
+                        transaction: transaction.clone(),
+                        is_padding: i >= prices.len(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut circuit = RollupCircuit {
+            slots,
+            analysis: AnalysisCircuit {
+                leaf_crh_params: data.leaf_crh_params,
+                two_to_one_crh_params: data.two_to_one_crh_params,
+                periods: Vec::new(),
+                ledger_root: None,
+                presence: None,
+                partition: vec![(0..transactions.len()).collect()],
+                rounding: RoundingMode::Floor,
+                output_purchase_price_subtotals: Vec::new(),
+                output_purchase_price_total: Amount::default(),
+                output_purchase_price_avg_range: None,
+                output_purchase_price_min: None,
+                output_purchase_price_max: None,
+                bounds_purchase_price_min: Amount::from_minor_units(0),
+                bounds_purchase_price_max: Amount::from_minor_units(1000),
+                bounds_com: None,
+            },
+        };
+        set_outputs(&mut circuit);
+        circuit
+    }
+
+    // Computes the outputs from the per-period totals, the same way as for committed periods.
+    // Absent periods count as 0 in the sums, and aren't candidates for the minimum or maximum.
+    fn set_outputs(circuit: &mut RollupCircuit) {
+        let present = |i: usize| {
+            let presence = circuit.analysis.presence.as_ref();
+            presence.is_none_or(|mask| mask.present[i])
+        };
+        let totals: Vec<Amount> = circuit
+            .period_totals()
+            .into_iter()
+            .enumerate()
+            .map(|(i, total)| if present(i) { total } else { Amount::default() })
+            .collect();
+        let present_totals = || totals.iter().enumerate().filter(|&(i, _)| present(i));
+        let min = present_totals().map(|(_, total)| *total).min();
+        let max = present_totals().map(|(_, total)| *total).max();
+
+        let analysis = &mut circuit.analysis;
+        analysis.output_purchase_price_subtotals = subtotals(&totals, &analysis.partition).unwrap();
+        analysis.output_purchase_price_total = analysis.output_purchase_price_subtotals[0];
+        analysis.output_purchase_price_min = min;
+        analysis.output_purchase_price_max = max;
+    }
+
+    // Three months of purchases. They add up to 200, 200 and 300, with 700 in total.
+    const PURCHASES: [&[u64]; 3] = [&[120, 80], &[50, 50, 100], &[300]];
+
+    // Correctness test: The monthly totals are proven from the transactions, and the analysis runs
+    // on them like on committed monthly prices
+    #[test]
+    fn rollup_correctness() {
+        let circuit = setup(&PURCHASES);
+        let totals = [200, 200, 300].map(Amount::from_minor_units);
+        assert_eq!(circuit.period_totals(), totals);
+        assert_eq!(circuit.analysis.output_purchase_price_total, Amount::from_minor_units(700));
+        assert!(is_satisfied(circuit.clone()), "rejected the rolled up totals");
+
+        // 12 slot commitments of 3 field elements each, plus the subtotal, the total, the minimum,
+        // the maximum and the two bounds. The padding flags aren't inputs at all.
+        let public_inputs = circuit.public_inputs().unwrap();
+        assert_eq!(public_inputs.len(), 3 * MAX_TRANSACTIONS * 3 + 6);
+
+        // A month with no transactions at all is a total of 0
+        let circuit = setup(&[&[120, 80], &[]]);
+        assert_eq!(circuit.analysis.output_purchase_price_min, Some(Amount::default()));
+        assert!(is_satisfied(circuit), "rejected an empty month");
+    }
+
+    // Dropped transaction test: The prover marks the 100 in the second month as padding, and
+    // lowers every output to match, so the claims are consistent with the flags. The slot is
+    // still opened and added up, so the totals don't match. A transaction that doesn't open is
+    // rejected too.
+    #[test]
+    fn rollup_dropped_transaction() {
+        let circuit = setup(&PURCHASES);
+
+        let mut dropped = circuit.clone();
+        dropped.slots[1][2].is_padding = true;
+        set_outputs(&mut dropped);
+        assert_eq!(dropped.period_totals()[1], Amount::from_minor_units(100));
+        assert_eq!(dropped.analysis.output_purchase_price_total, Amount::from_minor_units(600));
+        assert!(!is_satisfied(dropped), "accepted totals with a dropped transaction");
+
+        let mut bad_transaction = circuit;
+        bad_transaction.slots[0][0].transaction.data_purchase_price = Amount::from_minor_units(20);
+        set_outputs(&mut bad_transaction);
+        assert!(!is_satisfied(bad_transaction), "accepted a transaction that doesn't open");
+    }
+
+    // Absent month test: A month with only padding can be absent, and then it's left out of the
+    // minimum. A month with a transaction can't be, even if the outputs are computed without it
+    // and the mask is private.
+    #[test]
+    fn rollup_absent_month() {
+        let mut circuit = setup(&[&[120, 80], &[], &[300]]);
+        for (present, accepted) in [([true, false, true], true), ([true, true, false], false)] {
+            let mask = PresenceMask { present: present.to_vec(), is_public: false };
+            circuit.analysis.presence = Some(mask);
+            set_outputs(&mut circuit);
+            assert_eq!(is_satisfied(circuit.clone()), accepted, "wrong result for {present:?}");
+        }
+    }
+}
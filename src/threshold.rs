@@ -0,0 +1,170 @@
+use crate::{
+    amount::{Amount, AmountVar},
+    constraints::{CommittedPeriods, PublicInputs},
+    range::{enforce_bit_width, enforce_cmp_small, is_cmp_small, VALUE_BITS},
+    F, FV,
+};
+
+use ark_r1cs_std::{alloc::AllocVar, fields::FieldVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// A ZK circuit that proves "at least k of the n committed purchase prices are at or above X",
+/// e.g., income above a threshold in at least 9 of the last 12 months, without revealing the
+/// prices or which periods they were.
+#[derive(Clone)]
+pub struct ThresholdCountCircuit {
+    /// The committed data for every period, and the ledger it's in, if any.
+    pub data: CommittedPeriods,
+
+    // The analysis bounds
+    /// The threshold X, below `max_value_bound()`. Prices equal to it count as being above it.
+    pub bounds_threshold: Amount,
+    /// The number of prices k that must be at or above the threshold, below `max_value_bound()`
+    pub bounds_min_count: F,
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+impl PublicInputs for ThresholdCountCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                vec![self.bounds_threshold.0, self.bounds_min_count],
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for ThresholdCountCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // allocate and open the committed data, and check every price is within the bounds
+        let (data_purchase_prices, _) = self.data.open_prices_within_bounds(
+            cs.clone(),
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        // create inputs for the threshold values
+        let bounds_threshold = AmountVar::new_input(ns!(cs, "bounds threshold"), || {
+            Ok(&self.bounds_threshold)
+        })?
+        .0;
+        let bounds_min_count =
+            FV::new_input(ns!(cs, "bounds min count"), || Ok(&self.bounds_min_count))?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // The threshold and k are range checked like the prices, so comparing them is cheap
+        enforce_bit_width(cs.clone(), &bounds_threshold, VALUE_BITS)?;
+        enforce_bit_width(cs.clone(), &bounds_min_count, VALUE_BITS)?;
+
+        // count the prices at or above the threshold. This is the same comparison as the bounds
+        // checks, except that is_cmp_small returns the result as a bit instead of enforcing it.
+        let above_threshold = data_purchase_prices
+            .iter()
+            .map(|price| {
+                is_cmp_small(
+                    cs.clone(),
+                    price,
+                    &bounds_threshold,
+                    Ordering::Greater,
+                    true,
+                    VALUE_BITS,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let count = above_threshold
+            .into_iter()
+            .fold(FV::zero(), |acc, bit| acc + FV::from(bit));
+
+        // check the count. It's at most n, so it's below 2^64 too.
+        enforce_cmp_small(
+            cs,
+            &count,
+            &bounds_min_count,
+            Ordering::Greater,
+            true,
+            VALUE_BITS,
+        )?;
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constraints::max_value_bound,
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+
+    // Sets up a threshold count circuit over the given prices, claiming at least min_count of them
+    // are at or above the threshold
+    fn setup(prices: &[u64], threshold: u64, min_count: u64) -> ThresholdCountCircuit {
+        ThresholdCountCircuit {
+            data: gen_test_committed_prices(prices),
+            bounds_threshold: Amount::from_minor_units(threshold),
+            bounds_min_count: F::from(min_count),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+        }
+    }
+
+    // 9 of these 12 monthly incomes are at or above 500, and one of them is exactly 500
+    const INCOMES: [u64; 12] = [520, 610, 480, 500, 700, 530, 90, 640, 505, 560, 499, 800];
+
+    // Tie test: An income exactly at the threshold counts, so there are 9 at or above 500, but
+    // only 8 at or above 501. Any k up to the true count is accepted, and one more isn't.
+    #[test]
+    fn threshold_ties() {
+        for (threshold, count) in [(500, 9), (501, 8), (499, 10), (640, 3)] {
+            for min_count in [0, 1, count] {
+                let circuit = setup(&INCOMES, threshold, min_count);
+                assert!(is_satisfied(circuit), "rejected {min_count} of 12 above {threshold}");
+            }
+            let circuit = setup(&INCOMES, threshold, count + 1);
+            assert!(!is_satisfied(circuit), "accepted {} of 12 above {threshold}", count + 1);
+        }
+    }
+
+    // Extremes test: A threshold at the minimum counts every price, and one above the maximum
+    // counts none. k can be all n, but not n + 1.
+    #[test]
+    fn threshold_extremes() {
+        assert!(is_satisfied(setup(&INCOMES, 0, 12)), "rejected all 12 above 0");
+        assert!(!is_satisfied(setup(&INCOMES, 0, 13)), "accepted 13 of 12 above 0");
+        assert!(is_satisfied(setup(&INCOMES, 1001, 0)), "rejected none above the maximum");
+        assert!(!is_satisfied(setup(&INCOMES, 1001, 1)), "accepted one above the maximum");
+    }
+
+    // Wraparound test: k = −1 is p − 1 in the field, which a count can never reach. A threshold
+    // of 2^64 + 500 would compare like 500 if it weren't range checked, since the cheap
+    // comparisons only look at the low 64 bits of the difference.
+    #[test]
+    fn threshold_out_of_range() {
+        let mut circuit = setup(&INCOMES, 500, 0);
+        circuit.bounds_min_count = -F::from(1u32);
+        assert!(!is_satisfied(circuit), "accepted a negative k");
+
+        let mut circuit = setup(&INCOMES, 500, 0);
+        circuit.bounds_threshold = Amount(max_value_bound() + F::from(500u32));
+        assert!(!is_satisfied(circuit), "accepted a threshold that's too large");
+    }
+}
@@ -0,0 +1,271 @@
+use crate::{
+    amount::Amount,
+    constraints::{CommittedPeriods, PublicInputs},
+    econ_data::MAX_SLOPE_DECIMALS,
+    range::{enforce_bit_width, enforce_cmp_small},
+    F, FV,
+};
+
+use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::FieldVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// The claimed fixed-point slope must be in `[-2^127, 2^127)`. Along with the remainder check,
+/// this pins it down to the one integer that's the floor of the scaled slope.
+fn max_slope_bound() -> F {
+    F::from(1u128 << 127)
+}
+
+/// The bit width of the shifted fixed-point slope and of the remainder. The denominator is a
+/// `u128`, so a remainder below it also fits.
+const SLOPE_BITS: usize = 128;
+
+/// A ZK circuit that proves the least-squares slope of the committed purchase prices against
+/// their period index `t = 0, 1, ..., n - 1`, without revealing the prices.
+///
+/// The slope is the rational number `N / D`, where `N = n·Σt·x − Σt·Σx` and
+/// `D = n·Σt² − (Σt)²`. Since the t's are constants, `N` is a linear combination of the prices,
+/// and `D` only depends on `n`. Both are outputs, along with the slope in fixed point, i.e.,
+/// `floor(10^slope_decimals · N / D)`. The slope can be negative, so the numerator and
+/// fixed-point slope are signed. A negative value `-x` is the field element `p - x`. Use
+/// `econ_data::field_to_i128` to read them.
+#[derive(Clone)]
+pub struct TrendCircuit {
+    /// The committed data for every period, and the ledger it's in, if any. There must be at least
+    /// two periods for the slope to be defined.
+    pub data: CommittedPeriods,
+
+    /// The number of decimal digits of the fixed-point slope, at most
+    /// `econ_data::MAX_SLOPE_DECIMALS`. The scale is a constant in the circuit, so changing it
+    /// needs a new proving key.
+    pub slope_decimals: u32,
+
+    // The analysis outputs. Use `econ_data::linear_trend` to compute them.
+    /// `n·Σt·x − Σt·Σx`, which is signed
+    pub output_slope_numerator: F,
+    /// `n·Σt² − (Σt)²`
+    pub output_slope_denominator: F,
+    /// `floor(10^slope_decimals · numerator / denominator)`, which is signed
+    pub output_slope_fixed: F,
+
+    // The analysis bounds. The maximum must be below `max_value_bound()`.
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+impl PublicInputs for TrendCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                vec![
+                    self.output_slope_numerator,
+                    self.output_slope_denominator,
+                    self.output_slope_fixed,
+                ],
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for TrendCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // A slope needs at least two points, and the fixed-point scale can't be too large
+        let n = self.data.periods.len() as u128;
+        if n < 2 || self.slope_decimals > MAX_SLOPE_DECIMALS {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // allocate and open the committed data, and check every price is within the bounds.
+        // Keeping the prices below 2^64 keeps |N| far below the field size, so the signed values
+        // below don't wrap around.
+        let (data_purchase_prices, _) = self.data.open_prices_within_bounds(
+            cs.clone(),
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        // create inputs for the result values
+        let output_slope_numerator = FV::new_input(ns!(cs, "slope numerator result"), || {
+            Ok(&self.output_slope_numerator)
+        })?;
+        let output_slope_denominator = FV::new_input(ns!(cs, "slope denominator result"), || {
+            Ok(&self.output_slope_denominator)
+        })?;
+        let output_slope_fixed =
+            FV::new_input(ns!(cs, "slope result"), || Ok(&self.output_slope_fixed))?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // compute Σx and Σt·x. The t's are constants, so both are linear combinations and cost no
+        // constraints.
+        let sum: FV = data_purchase_prices.iter().sum();
+        let sum_t_x = data_purchase_prices
+            .iter()
+            .enumerate()
+            .map(|(t, price)| price * F::from(t as u64))
+            .collect::<Vec<_>>()
+            .iter()
+            .sum::<FV>();
+
+        // Σt and Σt² only depend on n
+        let sum_t = n * (n - 1) / 2;
+        let sum_t_sq = (n - 1) * n * (2 * n - 1) / 6;
+        let denominator = F::from(n * sum_t_sq - sum_t * sum_t);
+
+        // check numerator and denominator values
+        let computed_numerator = sum_t_x * F::from(n) - sum * F::from(sum_t);
+        computed_numerator.enforce_equal(&output_slope_numerator)?;
+        output_slope_denominator.enforce_equal(&FV::constant(denominator))?;
+
+        // check fixed-point slope value. We witness nothing here: s is the floor of scale·N / D
+        // iff r = scale·N − s·D is in [0, D). The range check on r also rules out a negative r,
+        // which would be close to p. Bounding s and the scale is what makes the check hold over
+        // the integers and not just mod p, see `MAX_SLOPE_DECIMALS`.
+        enforce_bit_width(cs.clone(), &(&output_slope_fixed + max_slope_bound()), SLOPE_BITS)?;
+        let scale = F::from(10u128.pow(self.slope_decimals));
+        let remainder = output_slope_numerator * scale - &output_slope_fixed * denominator;
+        enforce_bit_width(cs.clone(), &remainder, SLOPE_BITS)?;
+        enforce_cmp_small(
+            cs.clone(),
+            &remainder,
+            &FV::constant(denominator),
+            Ordering::Less,
+            false,
+            SLOPE_BITS,
+        )?;
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        econ_data::{field_to_i128, linear_trend},
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Sets up a legitimate trend circuit over the given prices
+    fn setup(prices: &[u64], slope_decimals: u32) -> TrendCircuit {
+        let data = gen_test_committed_prices(prices);
+        let prices = data.purchase_prices();
+        let (numerator, denominator, fixed) = linear_trend(&prices, slope_decimals).unwrap();
+
+        TrendCircuit {
+            data,
+            slope_decimals,
+            output_slope_numerator: numerator,
+            output_slope_denominator: denominator,
+            output_slope_fixed: fixed,
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+        }
+    }
+
+    // Exact slope test: 100, 110, 120, 130 rises by exactly 10 per period, which is 200 / 20, and
+    // 130, 120, 100, 100 falls by exactly 11. The remainder is 0 in both cases, so a fixed-point
+    // slope one too low leaves a remainder of exactly D, which is rejected.
+    #[test]
+    fn trend_exact() {
+        for (prices, expected) in [
+            ([100, 110, 120, 130], (200, 20, 1000)),
+            ([130, 120, 100, 100], (-220, 20, -1100)),
+        ] {
+            let circuit = setup(&prices, 2);
+            let claimed = (
+                field_to_i128(&circuit.output_slope_numerator).unwrap(),
+                field_to_i128(&circuit.output_slope_denominator).unwrap(),
+                field_to_i128(&circuit.output_slope_fixed).unwrap(),
+            );
+            assert_eq!(claimed, expected);
+            assert!(is_satisfied(circuit.clone()), "rejected the trend of {prices:?}");
+
+            for delta in [F::from(1u32), -F::from(1u32)] {
+                let mut bad_circuit = circuit.clone();
+                bad_circuit.output_slope_fixed += delta;
+                assert!(!is_satisfied(bad_circuit), "accepted a wrong fixed-point slope");
+            }
+        }
+    }
+
+    // Negative rounding test: 100, 101, 103 gives 9 / 6 = 1.5, which is 1 with no decimals and 15
+    // with one. Reversed, it's −1.5, which rounds down to −2 and not toward zero to −1.
+    #[test]
+    fn trend_negative_floor() {
+        for (prices, decimals, expected) in [
+            ([100, 101, 103], 0, (9, 6, 1)),
+            ([100, 101, 103], 1, (9, 6, 15)),
+            ([103, 101, 100], 0, (-9, 6, -2)),
+        ] {
+            let circuit = setup(&prices, decimals);
+            let claimed = (
+                field_to_i128(&circuit.output_slope_numerator).unwrap(),
+                field_to_i128(&circuit.output_slope_denominator).unwrap(),
+                field_to_i128(&circuit.output_slope_fixed).unwrap(),
+            );
+            assert_eq!(claimed, expected);
+            assert!(is_satisfied(circuit), "rejected the trend of {prices:?}");
+        }
+
+        let mut toward_zero = setup(&[103, 101, 100], 0);
+        toward_zero.output_slope_fixed = -F::from(1u32);
+        assert!(!is_satisfied(toward_zero), "accepted a slope rounded toward zero");
+
+        // The numerator and denominator are pinned down too, not just their ratio
+        let mut scaled = setup(&[103, 101, 100], 0);
+        scaled.output_slope_numerator = -F::from(18u32);
+        scaled.output_slope_denominator = F::from(12u32);
+        assert!(!is_satisfied(scaled), "accepted a scaled numerator and denominator");
+    }
+
+    // Length test: Two periods are the fewest with a slope, and then D = 2·1 − 1 = 1. Flat prices
+    // have a slope of 0 at any scale. A single period has no slope at all.
+    #[test]
+    fn trend_lengths() {
+        let circuit = setup(&[100, 105], 3);
+        assert_eq!(circuit.output_slope_denominator, F::from(1u32));
+        assert_eq!(circuit.output_slope_fixed, F::from(5000u32));
+        assert!(is_satisfied(circuit), "rejected a trend over two periods");
+
+        let circuit = setup(&[500, 500, 500, 500], MAX_SLOPE_DECIMALS);
+        assert_eq!(circuit.output_slope_fixed, F::from(0u32));
+        assert!(is_satisfied(circuit), "rejected a flat trend");
+
+        let mut circuit = setup(&[100, 105], 0);
+        circuit.data.periods.pop();
+        assert!(circuit.generate_constraints(ConstraintSystem::new_ref()).is_err());
+    }
+
+    // Too many decimals would let the scaled numerator wrap around the field, so they're rejected
+    // both natively and in the circuit
+    #[test]
+    fn trend_max_decimals() {
+        let prices = [100, 101, 103].map(Amount::from_minor_units);
+        assert!(linear_trend(&prices, MAX_SLOPE_DECIMALS).is_some());
+        assert_eq!(linear_trend(&prices, MAX_SLOPE_DECIMALS + 1), None);
+        assert_eq!(linear_trend(&prices, 39), None);
+
+        let mut circuit = setup(&[100, 101, 103], MAX_SLOPE_DECIMALS);
+        assert!(is_satisfied(circuit.clone()), "rejected the most decimals allowed");
+        circuit.slope_decimals = 39;
+        assert!(circuit.generate_constraints(ConstraintSystem::new_ref()).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use crate::{
-    econ_data::Data,
+    amount::Amount,
+    constraints::Period,
+    econ_data::{Data, RoundingMode},
     hash::{LeafHashParams, TwoToOneHashParams},
     merkle::{Leaf, SimpleMerkleTree},
     F,
@@ -13,14 +15,62 @@ use std::{
 
 use ark_ff::UniformRand;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::Rng;
 
 pub const POSSESSION_PK_FILENAME: &str = "possession_proving_key.bin";
 pub const POSSESSION_VK_FILENAME: &str = "possession_verifying_key.bin";
 pub const POSSESSION_PROOF_FILENAME: &str = "possession_proof.bin";
 pub const POSSESSION_REVEALED_SERIAL_FILENAME: &str = "possession_revealed_serial.bin";
+pub const POSSESSION_COMMITMENTS_FILENAME: &str = "possession_commitments.bin";
+pub const POSSESSION_OUTPUTS_FILENAME: &str = "possession_outputs.bin";
+
+pub const GROWTH_PK_FILENAME: &str = "growth_proving_key.bin";
+pub const GROWTH_VK_FILENAME: &str = "growth_verifying_key.bin";
+pub const GROWTH_PROOF_FILENAME: &str = "growth_proof.bin";
+pub const GROWTH_BASE_COMMITMENTS_FILENAME: &str = "growth_base_commitments.bin";
+pub const GROWTH_CURRENT_COMMITMENTS_FILENAME: &str = "growth_current_commitments.bin";
+pub const GROWTH_OUTPUTS_FILENAME: &str = "growth_outputs.bin";
 
 pub const PEDERSEN_PARAMS_FILENAME: &str = "pedersen_params.bin";
 
+/// The number of periods the analysis circuit is set up for, e.g., 4 quarters, 12 months or 52
+/// weeks. The proving key only works for circuits of this size, so `gen_params` and `prove` must
+/// agree on it.
+pub const NUM_PERIODS: usize = 12;
+
+/// The number of groups of consecutive periods the analysis circuit reports subtotals for, e.g., 4
+/// quarters of a 12-month year. It must divide `NUM_PERIODS`. Like `NUM_PERIODS`, this is baked
+/// into the proving key.
+pub const NUM_GROUPS: usize = 4;
+
+/// How the analysis circuit rounds the average. Like `NUM_PERIODS`, this is baked into the
+/// proving key.
+pub const ROUNDING: RoundingMode = RoundingMode::Floor;
+
+/// Whether the analysis circuit proves its data commitments are leaves of the public ledger,
+/// rather than revealing them as public inputs. Also baked into the proving key.
+pub const LEDGER_MODE: bool = true;
+
+/// Whether the analysis circuit allows periods to be missing, e.g., a month a store was closed.
+/// Missing periods drop out of every sum and count. Also baked into the proving key.
+pub const MISSING_PERIODS: bool = false;
+
+/// Whether the analysis circuit reveals which periods are missing, rather than only how many
+/// periods are present. Only used if `MISSING_PERIODS` is set. Also baked into the proving key.
+pub const PUBLIC_PRESENCE: bool = true;
+
+/// Whether the analysis circuit reveals the smallest and largest purchase prices alongside the
+/// average. Also baked into the proving key.
+pub const REVEAL_MIN_MAX: bool = true;
+
+/// Whether the analysis circuit keeps its price bounds private, and only reveals a commitment to
+/// them. Also baked into the proving key.
+pub const HIDDEN_BOUNDS: bool = false;
+
+/// Whether the analysis circuit keeps the average private, and only proves it's within a public
+/// interval. The interval itself is chosen when proving. Also baked into the proving key.
+pub const AVG_RANGE_ONLY: bool = false;
+
 /// A helper function that deterministically creates 16 baseball data and their commitment
 /// randomness
 fn all_data() -> Vec<(Data, F)> {
@@ -43,17 +93,17 @@ pub fn gen_test_tree(
 ) -> SimpleMerkleTree {
     let leaves: Vec<Leaf> = all_data()
         .into_iter()
-        .map(|(data, com_rand)| data.commit(&leaf_crh_params, &com_rand))
+        .map(|(data, com_rand)| data.commit(leaf_crh_params, &com_rand))
         .collect();
 
-    SimpleMerkleTree::new(&leaf_crh_params, &two_to_one_crh_params, leaves).unwrap()
+    SimpleMerkleTree::new(leaf_crh_params, two_to_one_crh_params, leaves).unwrap()
 }
 
 /// Unfortuantely you can't get leaves out of trees, so we need a separate function for returning
 /// the i-th leaf.
 pub fn get_test_leaf(leaf_crh_params: &LeafHashParams, i: usize) -> Leaf {
     let (data, com_rand) = all_data().get(i).unwrap().clone();
-    data.commit(&leaf_crh_params, &com_rand)
+    data.commit(leaf_crh_params, &com_rand)
 }
 
 /// Returns the i-th data and commitment randomness in the test tree.
@@ -61,6 +111,42 @@ pub fn get_test_data(i: usize) -> (Data, F) {
     all_data().get(i).unwrap().clone()
 }
 
+/// Commits to each of the given purchase prices with fresh randomness, and returns one period per
+/// price. Every period has a quantity of 1. The commitments are meant to be public, so no Merkle
+/// paths are filled in.
+pub fn gen_test_periods<R: Rng>(
+    leaf_crh_params: &LeafHashParams,
+    prices: &[Amount],
+    rng: &mut R,
+) -> Vec<Period> {
+    let data: Vec<Data> = prices
+        .iter()
+        .map(|&purchase_price| Data { purchase_price, quantity: F::from(1) })
+        .collect();
+    gen_test_periods_from_data(leaf_crh_params, &data, rng)
+}
+
+/// Commits to each of the given data with fresh randomness, and returns one period per data. The
+/// commitments are meant to be public, so no Merkle paths are filled in.
+pub fn gen_test_periods_from_data<R: Rng>(
+    leaf_crh_params: &LeafHashParams,
+    data: &[Data],
+    rng: &mut R,
+) -> Vec<Period> {
+    data.iter()
+        .map(|data| {
+            let data_com_rand = F::rand(rng);
+            Period {
+                com: data.commit(leaf_crh_params, &data_com_rand).to_vec(),
+                data_purchase_price: data.purchase_price,
+                data_quantity: data.quantity,
+                data_com_rand,
+                path: None,
+            }
+        })
+        .collect()
+}
+
 pub fn write_to_file<S: CanonicalSerialize>(path_str: &str, data: &S) {
     // Convert string to FS path
     let path = Path::new(path_str);
@@ -69,16 +155,17 @@ pub fn write_to_file<S: CanonicalSerialize>(path_str: &str, data: &S) {
     let mut f = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(path)
-        .expect(&format!("could not open {path_str} for writing"));
+        .unwrap_or_else(|_| panic!("could not open {path_str} for writing"));
 
     // Serialize the data
     let mut buf = Vec::new();
     data.serialize_compressed(&mut buf)
-        .expect(&format!("failed to serialize to {path_str}"));
+        .unwrap_or_else(|_| panic!("failed to serialize to {path_str}"));
 
     // Write to file
-    f.write(&buf).expect("failed to write to {path_str}");
+    f.write_all(&buf).unwrap_or_else(|_| panic!("failed to write to {path_str}"));
 }
 
 pub fn read_from_file<S: CanonicalDeserialize>(path_str: &str) -> S {
@@ -89,14 +176,66 @@ pub fn read_from_file<S: CanonicalDeserialize>(path_str: &str) -> S {
     let mut f = OpenOptions::new()
         .read(true)
         .open(path)
-        .expect(&format!("could not open {path_str} for reading"));
+        .unwrap_or_else(|_| panic!("could not open {path_str} for reading"));
 
     // Read from file
     let mut buf = Vec::new();
     f.read_to_end(&mut buf)
-        .expect(&format!("failed to read from {path_str}"));
+        .unwrap_or_else(|_| panic!("failed to read from {path_str}"));
 
     // Deserialize the data
     S::deserialize_compressed_unchecked(buf.as_slice())
-        .expect(&format!("failed to deserialize from {path_str}"))
+        .unwrap_or_else(|_| panic!("failed to deserialize from {path_str}"))
+}
+
+//
+// TEST HARNESS
+//
+
+/// The setup that the circuit tests share
+#[cfg(test)]
+pub(crate) mod test_harness {
+    use super::*;
+    use crate::{
+        constraints::{CommittedPeriods, PublicInputs},
+        hash::{LeafHash, TwoToOneHash},
+    };
+
+    use ark_crypto_primitives::crh::{CRHScheme, TwoToOneCRHScheme};
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    /// Generates fresh hash parameters, and commits to each of the given `(price, quantity)` pairs
+    /// with fresh randomness. The commitments are public, so there's no ledger root.
+    pub fn gen_test_committed_data(data: &[(u64, u64)]) -> CommittedPeriods {
+        let mut rng = ark_std::test_rng();
+        let leaf_crh_params = <LeafHash as CRHScheme>::setup(&mut rng).unwrap();
+        let two_to_one_crh_params = <TwoToOneHash as TwoToOneCRHScheme>::setup(&mut rng).unwrap();
+
+        let data: Vec<Data> = data
+            .iter()
+            .map(|&(price, quantity)| Data {
+                purchase_price: Amount::from_minor_units(price),
+                quantity: F::from(quantity),
+            })
+            .collect();
+        let periods = gen_test_periods_from_data(&leaf_crh_params, &data, &mut rng);
+
+        CommittedPeriods { leaf_crh_params, two_to_one_crh_params, periods, ledger_root: None }
+    }
+
+    /// Like `gen_test_committed_data`, where every period has a quantity of 1
+    pub fn gen_test_committed_prices(prices: &[u64]) -> CommittedPeriods {
+        let data: Vec<(u64, u64)> = prices.iter().map(|&price| (price, 1)).collect();
+        gen_test_committed_data(&data)
+    }
+
+    /// Checks whether the given circuit is satisfied on a fresh constraint system, and that its
+    /// public inputs are the ones a verifier would compute
+    pub fn is_satisfied(circuit: impl ConstraintSynthesizer<F> + PublicInputs) -> bool {
+        let public_inputs = circuit.public_inputs().unwrap();
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert_eq!(cs.borrow().unwrap().instance_assignment[1..], public_inputs[..]);
+        cs.is_satisfied().unwrap()
+    }
 }
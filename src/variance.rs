@@ -0,0 +1,206 @@
+use crate::{
+    amount::Amount,
+    constraints::{CommittedPeriods, PublicInputs},
+    F, FV,
+};
+
+use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::FieldVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// The claimed standard deviation must be below this, so that squaring it can't wrap around the
+/// field
+fn max_std_dev_bound() -> F {
+    F::from(1u128 << 126)
+}
+
+/// A ZK circuit that proves the dispersion of the committed purchase prices without revealing
+/// them. It opens the same period data as `AnalysisCircuit`, but instead of an average it proves
+/// the variance and, optionally, the standard deviation.
+///
+/// To avoid division, both outputs are scaled by the number of periods `n`. The verifier divides
+/// `output_variance_scaled` by `n²` to get the population variance.
+#[derive(Clone)]
+pub struct VarianceCircuit {
+    /// The committed data for every period, and the ledger it's in, if any.
+    pub data: CommittedPeriods,
+
+    // The analysis outputs
+    /// `n·Σx² − (Σx)²`, i.e., `n²` times the population variance. Use
+    /// `econ_data::scaled_variance` to compute it.
+    pub output_variance_scaled: F,
+    /// `floor(sqrt(output_variance_scaled))`, i.e., `n` times the population standard deviation,
+    /// rounded down. Use `econ_data::floor_sqrt` to compute it. If this is `None`, the standard
+    /// deviation isn't proven, and the proving key has no input for it.
+    pub output_std_dev_scaled: Option<F>,
+
+    // The analysis bounds. The maximum must be below `max_value_bound()`.
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+impl PublicInputs for VarianceCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                vec![self.output_variance_scaled],
+                self.output_std_dev_scaled.into_iter().collect(),
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for VarianceCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // allocate and open the committed data, and check every price is within the bounds.
+        // Keeping the maximum below 2^64 is what keeps everything below from wrapping around the
+        // field.
+        let (data_purchase_prices, _) = self.data.open_prices_within_bounds(
+            cs.clone(),
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        // create inputs for the result values
+        let output_variance_scaled = FV::new_input(ns!(cs, "variance result"), || {
+            Ok(&self.output_variance_scaled)
+        })?;
+        let output_std_dev_scaled = self
+            .output_std_dev_scaled
+            .as_ref()
+            .map(|std_dev| FV::new_input(ns!(cs, "std dev result"), || Ok(std_dev)))
+            .transpose()?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // compute Σx and Σx². The sum is a linear combination, and each square is one constraint.
+        let n = F::from(self.data.periods.len() as u64);
+        let sum: FV = data_purchase_prices.iter().sum();
+        let sum_of_squares = data_purchase_prices
+            .iter()
+            .map(|price| price.square())
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .sum::<FV>();
+
+        // check variance value
+        let computed_variance_scaled = sum_of_squares * n - sum.square()?;
+        computed_variance_scaled.enforce_equal(&output_variance_scaled)?;
+
+        // check standard deviation value, if there is one. The claimed root s is a floor square
+        // root iff s² <= variance < (s + 1)².
+        if let Some(std_dev) = output_std_dev_scaled {
+            std_dev.enforce_cmp(&FV::constant(max_std_dev_bound()), Ordering::Less, false)?;
+            std_dev
+                .square()?
+                .enforce_cmp(&output_variance_scaled, Ordering::Less, true)?;
+            (std_dev + FV::one())
+                .square()?
+                .enforce_cmp(&output_variance_scaled, Ordering::Greater, false)?;
+        }
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constraints::max_value_bound,
+        econ_data::{floor_sqrt, scaled_variance},
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+
+    // Sets up a legitimate variance circuit over the given prices
+    fn setup(prices: &[u64], prove_std_dev: bool) -> VarianceCircuit {
+        let data = gen_test_committed_prices(prices);
+        let prices = data.purchase_prices();
+
+        let variance_scaled = scaled_variance(&prices).unwrap();
+        let std_dev_scaled = floor_sqrt(&variance_scaled).unwrap();
+
+        VarianceCircuit {
+            data,
+            output_variance_scaled: variance_scaled,
+            output_std_dev_scaled: prove_std_dev.then_some(std_dev_scaled),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+        }
+    }
+
+    // Floor square root test: For 100, 200, 300, 400 we have n = 4, Σx = 1000 and Σx² = 300000. So
+    // the scaled variance is 4·300000 − 1000² = 200000, which isn't a square. Its floor square
+    // root is 447, and neither neighbor is accepted.
+    #[test]
+    fn variance_floor_sqrt() {
+        let circuit = setup(&[100, 200, 300, 400], true);
+        assert_eq!(circuit.output_variance_scaled, F::from(200000u32));
+        assert_eq!(circuit.output_std_dev_scaled, Some(F::from(447u32)));
+        assert!(is_satisfied(circuit.clone()), "rejected the floor square root");
+
+        for bad_std_dev in [446u32, 448] {
+            let mut bad_circuit = circuit.clone();
+            bad_circuit.output_std_dev_scaled = Some(F::from(bad_std_dev));
+            assert!(!is_satisfied(bad_circuit), "accepted {bad_std_dev} as the standard deviation");
+        }
+
+        let mut bad_variance = circuit;
+        bad_variance.output_variance_scaled += F::from(1u32);
+        assert!(!is_satisfied(bad_variance), "accepted the wrong variance");
+    }
+
+    // Perfect square test: For 100 and 300, the scaled variance is 2·100000 − 400² = 40000, whose
+    // square root 200 is exact. That's the case where s² <= variance holds with equality.
+    #[test]
+    fn variance_perfect_square() {
+        let circuit = setup(&[100, 300], true);
+        assert_eq!(circuit.output_std_dev_scaled, Some(F::from(200u32)));
+        assert!(is_satisfied(circuit.clone()), "rejected an exact square root");
+
+        for bad_std_dev in [199u32, 201] {
+            let mut bad_circuit = circuit.clone();
+            bad_circuit.output_std_dev_scaled = Some(F::from(bad_std_dev));
+            assert!(!is_satisfied(bad_circuit), "accepted {bad_std_dev} as the standard deviation");
+        }
+    }
+
+    // Constant test: Prices that never change have no dispersion, with or without the standard
+    // deviation. A variance of 0 also can't be claimed to be p − 1, i.e., −1.
+    #[test]
+    fn variance_constant_prices() {
+        for prove_std_dev in [true, false] {
+            let circuit = setup(&[500, 500, 500], prove_std_dev);
+            assert_eq!(circuit.output_variance_scaled, F::from(0u32));
+            assert!(is_satisfied(circuit.clone()), "rejected a variance of 0");
+
+            let mut bad_circuit = circuit;
+            bad_circuit.output_variance_scaled = -F::from(1u32);
+            assert!(!is_satisfied(bad_circuit), "accepted a negative variance");
+        }
+    }
+
+    // Bounds test: A maximum that's too large could let the sum of squares wrap around the field,
+    // so the circuit must reject it even if every price is in range.
+    #[test]
+    fn variance_bounds() {
+        let mut circuit = setup(&[100, 200, 300, 400], false);
+        circuit.bounds_purchase_price_max = Amount(max_value_bound());
+        assert!(!is_satisfied(circuit), "accepted a maximum that's too large");
+    }
+}
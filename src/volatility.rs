@@ -0,0 +1,254 @@
+use crate::{
+    amount::Amount,
+    constraints::{CommittedPeriods, PublicInputs},
+    econ_data::field_to_i128,
+    range::{enforce_bit_width, enforce_cmp_small, VALUE_BITS},
+    F, FV,
+};
+
+use ark_r1cs_std::{
+    alloc::AllocVar, boolean::Boolean, eq::EqGadget, fields::FieldVar, select::CondSelectGadget,
+    R1CSVar,
+};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// The most periods a volatility circuit can have. With prices below 2^64, every `n·x_i` is then
+/// below 2^74, so the deviations fit in `DEVIATION_BITS`.
+pub const MAX_VOLATILITY_PERIODS: usize = 1024;
+
+/// The bit width of the scaled deviations `|n·x_i − S|`
+const DEVIATION_BITS: usize = VALUE_BITS + 10;
+
+/// The bit width of both sides of every comparison below. The largest is `k·Σd_j`, which is below
+/// 2^64 · 2^84 = 2^148.
+const PRODUCT_BITS: usize = 160;
+
+/// A ZK circuit that proves a committed series isn't volatile, without revealing the prices or
+/// their mean. It proves two things:
+///
+/// 1. No period deviates from the mean by more than k times the mean absolute deviation (MAD).
+///    With `n` prices summing to `S`, price `x_i` is `d_i / n` from the mean, where
+///    `d_i = |n·x_i − S|`, and the MAD is `Σd_j / n²`. So the circuit checks
+///    `100 · n · d_i <= k · Σd_j`, where k is given in percent.
+/// 2. No price changes by more than p percent from one period to the next. The circuit checks
+///    `−p·x_{t−1} <= 100·(x_t − x_{t−1}) <= p·x_{t−1}`, with the terms moved around so neither side
+///    is ever negative.
+///
+/// Use `econ_data::deviation_percent` and `econ_data::change_percent` to find bounds that hold.
+#[derive(Clone)]
+pub struct VolatilityCircuit {
+    /// The committed data for every period, and the ledger it's in, if any. There can be at most
+    /// `MAX_VOLATILITY_PERIODS` periods.
+    pub data: CommittedPeriods,
+
+    // The analysis bounds. All of them must be below `max_value_bound()`.
+    /// How far any price may be from the mean, in percent of the MAD, e.g., 300 for 3 times
+    pub bounds_deviation_percent: F,
+    /// How much a price may change from the one before it, in percent of the earlier price
+    pub bounds_change_percent: F,
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+}
+
+impl PublicInputs for VolatilityCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                vec![self.bounds_deviation_percent, self.bounds_change_percent],
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for VolatilityCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        let num_periods = self.data.periods.len();
+        if num_periods == 0 || num_periods > MAX_VOLATILITY_PERIODS {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // allocate and open the committed data, and check every price is within the bounds
+        let (data_purchase_prices, _) = self.data.open_prices_within_bounds(
+            cs.clone(),
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        // create inputs for the threshold values
+        let bounds_deviation_percent = FV::new_input(ns!(cs, "bounds deviation"), || {
+            Ok(&self.bounds_deviation_percent)
+        })?;
+        let bounds_change_percent =
+            FV::new_input(ns!(cs, "bounds change"), || Ok(&self.bounds_change_percent))?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // bounds constraints. The prices are already range checked, and so are the percentages
+        // here, so none of the products below can wrap around the field.
+        enforce_bit_width(cs.clone(), &bounds_deviation_percent, VALUE_BITS)?;
+        enforce_bit_width(cs.clone(), &bounds_change_percent, VALUE_BITS)?;
+
+        // compute the scaled deviation d_i = |n·x_i − S| of every price. We witness d_i and the
+        // sign of n·x_i − S, and check that the sign applied to d_i gives back n·x_i − S. Since d_i
+        // is range checked, it's the only absolute value that does.
+        let n = F::from(num_periods as u64);
+        let sum: FV = data_purchase_prices.iter().sum();
+        let mut abs_deviations = Vec::with_capacity(num_periods);
+        for data_purchase_price in &data_purchase_prices {
+            let deviation = data_purchase_price * n - &sum;
+            let deviation_value = deviation.value().map(|d| match field_to_i128(&d) {
+                Some(d) => (F::from(d.unsigned_abs()), d < 0),
+                None => (d, false),
+            });
+
+            let abs_deviation = FV::new_witness(ns!(cs, "abs deviation"), || {
+                deviation_value.map(|(abs, _)| abs)
+            })?;
+            let is_negative = Boolean::new_witness(ns!(cs, "deviation sign"), || {
+                deviation_value.map(|(_, is_negative)| is_negative)
+            })?;
+
+            // CHECK: d_i is n·x_i − S with its sign removed
+            enforce_bit_width(cs.clone(), &abs_deviation, DEVIATION_BITS)?;
+            let signed_deviation =
+                FV::conditionally_select(&is_negative, &abs_deviation.negate()?, &abs_deviation)?;
+            signed_deviation.enforce_equal(&deviation)?;
+
+            abs_deviations.push(abs_deviation);
+        }
+
+        // check no price is an outlier. The 100·n·d_i side is below 2^91, and k·Σd_j is below
+        // 2^148.
+        let total_deviation: FV = abs_deviations.iter().sum();
+        let allowed_deviation = &bounds_deviation_percent * &total_deviation;
+        for abs_deviation in &abs_deviations {
+            let scaled_deviation = abs_deviation * (n * F::from(100u32));
+            enforce_cmp_small(
+                cs.clone(),
+                &scaled_deviation,
+                &allowed_deviation,
+                Ordering::Less,
+                true,
+                PRODUCT_BITS,
+            )?;
+        }
+
+        // check every change from one period to the next. We check
+        // 100·x_t <= 100·x_{t−1} + p·x_{t−1} and 100·x_{t−1} <= 100·x_t + p·x_{t−1}, which together
+        // are |x_t − x_{t−1}| <= p% of x_{t−1}.
+        let hundred = F::from(100u32);
+        for pair in data_purchase_prices.windows(2) {
+            let (prev, next) = (&pair[0], &pair[1]);
+            let allowed_change = prev * &bounds_change_percent;
+            enforce_cmp_small(
+                cs.clone(),
+                &(next * hundred),
+                &(prev * hundred + &allowed_change),
+                Ordering::Less,
+                true,
+                PRODUCT_BITS,
+            )?;
+            enforce_cmp_small(
+                cs.clone(),
+                &(prev * hundred),
+                &(next * hundred + &allowed_change),
+                Ordering::Less,
+                true,
+                PRODUCT_BITS,
+            )?;
+        }
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        econ_data::{change_percent, deviation_percent},
+        util::test_harness::{gen_test_committed_prices, is_satisfied},
+    };
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Sets up a volatility circuit over the given prices with the given bounds, in percent
+    fn setup(prices: &[u64], deviation_percent: u64, change_percent: u64) -> VolatilityCircuit {
+        VolatilityCircuit {
+            data: gen_test_committed_prices(prices),
+            bounds_deviation_percent: F::from(deviation_percent),
+            bounds_change_percent: F::from(change_percent),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+        }
+    }
+
+    // 100, 110, 99, 91 sum to 400, so the mean is 100. Scaled by n = 4, the deviations are 0, 40,
+    // 4 and 36, which sum to 80, so the furthest price is 100 · 4 · 40 / 80 = 200% of the MAD from
+    // the mean. The largest change is from 110 to 99, which is exactly 10%.
+    const PRICES: [u64; 4] = [100, 110, 99, 91];
+
+    // Tightness test: Both bounds of PRICES are met with equality, so they're accepted along with
+    // anything looser, and one less than either is rejected
+    #[test]
+    fn volatility_tight_bounds() {
+        let prices = PRICES.map(Amount::from_minor_units);
+        assert_eq!(deviation_percent(&prices), Some(F::from(200u32)));
+        assert_eq!(change_percent(&prices), Some(F::from(10u32)));
+
+        for (deviation, change) in [(200, 10), (300, 10), (200, 50)] {
+            let circuit = setup(&PRICES, deviation, change);
+            assert!(is_satisfied(circuit), "rejected a deviation of {deviation}%, {change}%");
+        }
+        for (deviation, change) in [(199, 10), (200, 9)] {
+            let circuit = setup(&PRICES, deviation, change);
+            assert!(!is_satisfied(circuit), "accepted a deviation of {deviation}%, {change}%");
+        }
+    }
+
+    // Direction test: A change is measured against the earlier price, so it's not symmetric. 100
+    // to 89 is 11% of 100, but 89 to 100 is 11 / 89 ≈ 12.4% of 89, which needs a bound of 13.
+    #[test]
+    fn volatility_change_direction() {
+        assert_eq!(change_percent(&[100, 89].map(Amount::from_minor_units)), Some(F::from(11u32)));
+        assert_eq!(change_percent(&[89, 100].map(Amount::from_minor_units)), Some(F::from(13u32)));
+
+        for (prices, tightest) in [([100, 89], 11), ([89, 100], 13)] {
+            assert!(is_satisfied(setup(&prices, 1000, tightest)), "rejected {prices:?}");
+            let circuit = setup(&prices, 1000, tightest - 1);
+            assert!(!is_satisfied(circuit), "accepted {prices:?} as a change of {}%", tightest - 1);
+        }
+
+        // Any rise from zero is an unbounded change
+        let circuit = setup(&[0, 1], 1000, 1_000_000);
+        assert!(!is_satisfied(circuit), "accepted a change from zero");
+    }
+
+    // Degenerate test: A flat series and a single period never deviate or change, so their MAD
+    // is 0 and bounds of 0 hold. With no periods there's nothing to prove.
+    #[test]
+    fn volatility_degenerate_series() {
+        for prices in [&[500, 500, 500][..], &[500][..], &[0, 0][..]] {
+            let circuit = setup(prices, 0, 0);
+            assert!(is_satisfied(circuit), "rejected {prices:?} with bounds of 0");
+        }
+
+        let circuit = setup(&[], 0, 0);
+        assert!(circuit.generate_constraints(ConstraintSystem::new_ref()).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{
+    amount::{Amount, AmountVar},
+    constraints::{CommittedPeriods, PublicInputs},
+    econ_data::RoundingMode,
+    range::{enforce_bit_width, enforce_cmp_small, VALUE_BITS},
+    F, FV,
+};
+
+use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::FieldVar, R1CSVar};
+use ark_relations::{
+    ns,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use core::cmp::Ordering;
+
+/// A ZK circuit that proves the quantity-weighted average price `Σ(q·p) / Σq` of the committed
+/// data, i.e., the total amount spent divided by the total number of units bought, without
+/// revealing any prices or quantities.
+#[derive(Clone)]
+pub struct WeightedAverageCircuit {
+    /// The committed data for every period, and the ledger it's in, if any.
+    pub data: CommittedPeriods,
+
+    /// How the average is rounded
+    pub rounding: RoundingMode,
+
+    // The analysis output
+    /// The weighted average price, rounded according to `rounding`. Use
+    /// `econ_data::weighted_average` to compute it.
+    pub output_weighted_avg_price: Amount,
+
+    // The analysis bounds. The maximums must be below `max_value_bound()`.
+    pub bounds_purchase_price_min: Amount,
+    pub bounds_purchase_price_max: Amount,
+    pub bounds_quantity_max: F,
+}
+
+impl PublicInputs for WeightedAverageCircuit {
+    fn public_inputs(&self) -> Option<Vec<F>> {
+        Some(
+            [
+                self.data.public_inputs_within_bounds(
+                    self.bounds_purchase_price_min,
+                    self.bounds_purchase_price_max,
+                )?,
+                vec![self.output_weighted_avg_price.0, self.bounds_quantity_max],
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl ConstraintSynthesizer<F> for WeightedAverageCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        // allocate and open the committed data, and check every price is within the bounds
+        let (data_vars, (_, bounds_purchase_price_max)) = self.data.open_within_bounds(
+            cs.clone(),
+            self.bounds_purchase_price_min,
+            self.bounds_purchase_price_max,
+        )?;
+
+        // create input for the result value
+        let output_weighted_avg_price = AmountVar::new_input(ns!(cs, "weighted avg result"), || {
+            Ok(&self.output_weighted_avg_price)
+        })?
+        .0;
+
+        // create input for the quantity bound
+        let bounds_quantity_max =
+            FV::new_input(ns!(cs, "bounds quantity"), || Ok(&self.bounds_quantity_max))?;
+
+        //
+        // Ok everything has been inputted. Now we do the logic of the circuit.
+        //
+
+        // quantity bounds. The prices are already range checked to VALUE_BITS, and so are the
+        // quantities here, so every q·p is below 2^128 and none of the sums below can wrap around
+        // the field.
+        enforce_bit_width(cs.clone(), &bounds_quantity_max, VALUE_BITS)?;
+        for data_var in &data_vars {
+            let quantity = &data_var.quantity;
+            enforce_bit_width(cs.clone(), quantity, VALUE_BITS)?;
+            enforce_cmp_small(
+                cs.clone(),
+                quantity,
+                &bounds_quantity_max,
+                Ordering::Less,
+                true,
+                VALUE_BITS,
+            )?;
+        }
+
+        // compute the total cost Σ(q·p), which is one constraint per period, and the total
+        // quantity Σq
+        let costs = data_vars
+            .iter()
+            .map(|data_var| &data_var.quantity * &data_var.amount.0)
+            .collect::<Vec<_>>();
+        let total_cost: FV = costs.iter().sum();
+        let total_quantity: FV = data_vars.iter().map(|data_var| &data_var.quantity).sum();
+
+        // check average value. Like in AnalysisCircuit, we witness the remainder r and check that
+        // numerator = avg * denominator + r with 0 <= r < denominator. Rounding to the nearest
+        // integer uses numerator = 2·cost + quantity and denominator = 2·quantity. Since r >= 0,
+        // r < denominator also means the total quantity isn't zero.
+        let (numerator, denominator) = match self.rounding {
+            RoundingMode::Nearest => (
+                total_cost.double()? + &total_quantity,
+                total_quantity.double()?,
+            ),
+            RoundingMode::Floor | RoundingMode::Exact => (total_cost, total_quantity),
+        };
+
+        let remainder = FV::new_witness(ns!(cs, "weighted avg remainder"), || {
+            Ok(numerator.value()? - self.output_weighted_avg_price.0 * denominator.value()?)
+        })?;
+
+        let computed_numerator = &output_weighted_avg_price * &denominator + &remainder;
+        computed_numerator.enforce_equal(&numerator)?;
+
+        match self.rounding {
+            RoundingMode::Exact => remainder.enforce_equal(&FV::zero())?,
+            RoundingMode::Floor | RoundingMode::Nearest => {
+                remainder.enforce_cmp(&denominator, Ordering::Less, false)?
+            }
+        }
+        denominator.enforce_cmp(&FV::zero(), Ordering::Greater, false)?;
+        output_weighted_avg_price.enforce_cmp(
+            &bounds_purchase_price_max,
+            Ordering::Less,
+            true,
+        )?;
+
+        // All done with the checks
+        Ok(())
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        constraints::max_value_bound,
+        econ_data::{weighted_average, Data},
+        util::test_harness::{gen_test_committed_data, is_satisfied},
+    };
+
+    // Sets up a legitimate weighted average circuit over the given (price, quantity) pairs
+    fn setup(pairs: &[(u64, u64)], rounding: RoundingMode) -> WeightedAverageCircuit {
+        let data: Vec<Data> = pairs
+            .iter()
+            .map(|&(price, quantity)| Data {
+                purchase_price: Amount::from_minor_units(price),
+                quantity: F::from(quantity),
+            })
+            .collect();
+
+        WeightedAverageCircuit {
+            data: gen_test_committed_data(pairs),
+            rounding,
+            output_weighted_avg_price: weighted_average(&data, rounding).unwrap_or_default(),
+            bounds_purchase_price_min: Amount::from_minor_units(0),
+            bounds_purchase_price_max: Amount::from_minor_units(1000),
+            bounds_quantity_max: F::from(1000),
+        }
+    }
+
+    // Rounding test: 10 units at 100 and 30 units at 200 cost 7000 for 40 units, so the weighted
+    // average is 175 exactly, whereas the plain average would be 150. Adding 1 unit at 900 gives
+    // 7900 for 41 units, or 192.68. Neighboring averages are rejected.
+    #[test]
+    fn weighted_avg_rounding() {
+        for (data, rounding, expected_avg) in [
+            (&[(100, 10), (200, 30)][..], RoundingMode::Exact, 175u64),
+            (&[(100, 10), (200, 30), (900, 1)][..], RoundingMode::Floor, 192),
+            (&[(100, 10), (200, 30), (900, 1)][..], RoundingMode::Nearest, 193),
+        ] {
+            let circuit = setup(data, rounding);
+            assert_eq!(circuit.output_weighted_avg_price, Amount::from_minor_units(expected_avg));
+            assert!(is_satisfied(circuit.clone()), "{rounding:?} weighted average failed");
+
+            for delta in [F::from(1u32), -F::from(1u32)] {
+                let mut bad_circuit = circuit.clone();
+                bad_circuit.output_weighted_avg_price.0 += delta;
+                assert!(!is_satisfied(bad_circuit), "{rounding:?} accepted a wrong average");
+            }
+        }
+    }
+
+    // Half test: 3 units at 100 and 3 at 101 average to exactly 100.5. Rounding to the nearest
+    // uses the doubled numerator 2·603 + 6 over 12, so the half rounds up to 101. An exact average
+    // doesn't exist, so neither neighbor is accepted.
+    #[test]
+    fn weighted_avg_half() {
+        let pairs = [(100, 3), (101, 3)];
+        for (rounding, expected_avg) in [(RoundingMode::Nearest, 101), (RoundingMode::Floor, 100)] {
+            let circuit = setup(&pairs, rounding);
+            assert_eq!(circuit.output_weighted_avg_price, Amount::from_minor_units(expected_avg));
+            assert!(is_satisfied(circuit), "{rounding:?} rejected the half");
+        }
+
+        for claimed_avg in [100u64, 101] {
+            let mut inexact = setup(&pairs, RoundingMode::Exact);
+            inexact.output_weighted_avg_price = Amount::from_minor_units(claimed_avg);
+            assert!(!is_satisfied(inexact), "accepted {claimed_avg} as an exact average");
+        }
+    }
+
+    // Zero quantity test: A period where nothing was bought doesn't move the average, whatever its
+    // price, but that price still has to be within the bounds. With no units at all there's
+    // nothing to average.
+    #[test]
+    fn weighted_avg_zero_quantity() {
+        let circuit = setup(&[(100, 10), (900, 0), (200, 30)], RoundingMode::Exact);
+        assert_eq!(circuit.output_weighted_avg_price, Amount::from_minor_units(175));
+        assert!(is_satisfied(circuit), "rejected a period with no units");
+
+        let mut circuit = setup(&[(100, 10), (1001, 0), (200, 30)], RoundingMode::Exact);
+        circuit.output_weighted_avg_price = Amount::from_minor_units(175);
+        assert!(!is_satisfied(circuit), "accepted an out of bounds price with no units");
+
+        let circuit = setup(&[(100, 0), (200, 0)], RoundingMode::Floor);
+        assert!(!is_satisfied(circuit), "accepted an average over zero units");
+    }
+
+    // Quantity bounds test: Quantities above the public maximum are rejected, and so is a maximum
+    // that's large enough for q·p to wrap around the field
+    #[test]
+    fn weighted_avg_quantity_bounds() {
+        let mut circuit = setup(&[(100, 10), (200, 30)], RoundingMode::Exact);
+        circuit.bounds_quantity_max = F::from(30u32);
+        assert!(is_satisfied(circuit.clone()), "rejected a quantity at the maximum");
+        circuit.bounds_quantity_max = F::from(29u32);
+        assert!(!is_satisfied(circuit), "accepted a quantity above the maximum");
+
+        let mut circuit = setup(&[(100, 10), (200, 30)], RoundingMode::Exact);
+        circuit.bounds_quantity_max = max_value_bound();
+        assert!(!is_satisfied(circuit), "accepted a maximum quantity that's too large");
+    }
+}